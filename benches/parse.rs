@@ -0,0 +1,53 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use acf::parser;
+
+/// A document with `entries` keys, mixing scalars, maps and sequences.
+fn document(entries: usize) -> String {
+    (0..entries)
+        .map(|index| match index % 4 {
+            0 => format!("key{index}={index}"),
+            1 => format!("key{index}=\"value {index} with \\\"quotes\\\"\""),
+            2 => {
+                format!("key{index}={{host: h{index}, port: {index}, ratio: 0.{index}, tls: true}}")
+            }
+            _ => format!("key{index}={{1, 2.5, text, !secret {index}}}"),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+
+    for entries in [100, 10_000] {
+        let input = document(entries);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("two_stage", entries),
+            &input,
+            |b, input| {
+                b.iter(|| acf::tokenized_to_config(input, parser::tokenize_ast(input).unwrap()))
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("single_pass", entries),
+            &input,
+            |b, input| b.iter(|| parser::parse(input).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("handwritten", entries),
+            &input,
+            |b, input| b.iter(|| acf::handwritten::parse(input).unwrap()),
+        );
+        group.bench_with_input(BenchmarkId::new("borrowed", entries), &input, |b, input| {
+            b.iter(|| acf::ACFRef::parse(input).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,22 @@
+//! Language server for ACF documents over stdio, see [`acf::lsp`].
+
+use std::process::ExitCode;
+
+use lsp_server::Connection;
+
+fn main() -> ExitCode {
+    let (connection, io_threads) = Connection::stdio();
+    let result = acf::lsp::run(&connection);
+    drop(connection);
+
+    match result
+        .map_err(|e| e.to_string())
+        .and_then(|()| io_threads.join().map_err(|e| e.to_string()))
+    {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("acf-lsp: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
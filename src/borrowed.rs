@@ -0,0 +1,242 @@
+//! [`ACFRef`], a config that borrows its keys and strings from the input instead of
+//! allocating them. Only strings with escape sequences or quotes inside are copied.
+
+use std::borrow::Cow;
+
+use ordered_float::OrderedFloat;
+use serde::de::value::{MapAccessDeserializer, MapDeserializer, SeqDeserializer};
+use serde::de::{self, Deserialize, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use snailquote::unescape;
+
+use crate::de::Error;
+use crate::parser::{self, parse_float, parse_integer};
+use crate::{Map, Secret, ACF};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ACFRef<'a> {
+    String(Cow<'a, str>),
+    Integer(i64),
+    Float(OrderedFloat<f64>),
+    Boolean(bool),
+    Secret(Secret<Box<ACFRef<'a>>>),
+    Seq(Vec<ACFRef<'a>>),
+    Map(Map<Cow<'a, str>, ACFRef<'a>>),
+}
+
+impl<'a> ACFRef<'a> {
+    pub fn parse(input: &'a str) -> Result<ACFRef<'a>, usize> {
+        let tokens = parser::tokenize_ast(input).map_err(|e| e.offset())?;
+        Ok(tokenized_to_ref(input, tokens))
+    }
+
+    pub fn into_owned(self) -> ACF {
+        ACF::from(self)
+    }
+}
+
+/// Like [`crate::tokenized_to_config`], but borrowing from `input` where possible.
+pub fn tokenized_to_ref(input: &str, tokens: parser::ACF) -> ACFRef<'_> {
+    match tokens {
+        parser::ACF::Boolean(range) => ACFRef::Boolean(&input[range] == "true"),
+        parser::ACF::Integer(range) => {
+            ACFRef::Integer(parse_integer(&input[range]).expect("tokenizer checked this"))
+        }
+        parser::ACF::Float(range) => ACFRef::Float(OrderedFloat::from(
+            parse_float(&input[range]).expect("tokenizer checked this"),
+        )),
+        parser::ACF::String(range) => ACFRef::String(unescape_borrowed(&input[range])),
+        parser::ACF::Secret(_, value) => {
+            ACFRef::Secret(Secret::new(Box::new(tokenized_to_ref(input, *value))))
+        }
+        parser::ACF::Seq(_, values) => ACFRef::Seq(
+            values
+                .into_iter()
+                .map(|value| tokenized_to_ref(input, value))
+                .collect(),
+        ),
+        parser::ACF::Map(_, map_values) => ACFRef::Map(
+            map_values
+                .into_iter()
+                .map(|(key, value)| {
+                    (
+                        unescape_borrowed(&input[key]),
+                        tokenized_to_ref(input, value),
+                    )
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// Borrows plain and simply quoted text, and falls back to `snailquote` for the rest.
+pub(crate) fn unescape_borrowed(text: &str) -> Cow<'_, str> {
+    let special = |text: &str| text.contains(['\\', '"', '\'']);
+
+    if !special(text) {
+        return Cow::Borrowed(text);
+    }
+    for quote in ['"', '\''] {
+        if let Some(inner) = text
+            .strip_prefix(quote)
+            .and_then(|text| text.strip_suffix(quote))
+        {
+            if !special(inner) {
+                return Cow::Borrowed(inner);
+            }
+        }
+    }
+    Cow::Owned(unescape(text).unwrap_or_default())
+}
+
+impl From<ACFRef<'_>> for ACF {
+    fn from(value: ACFRef<'_>) -> Self {
+        match value {
+            ACFRef::String(string) => ACF::from(string.into_owned()),
+            ACFRef::Integer(integer) => ACF::Integer(integer),
+            ACFRef::Float(float) => ACF::Float(float),
+            ACFRef::Boolean(boolean) => ACF::Boolean(boolean),
+            ACFRef::Secret(secret) => {
+                ACF::Secret(Secret::new(Box::new(ACF::from(*secret.into_exposed()))))
+            }
+            ACFRef::Seq(values) => ACF::Seq(values.into_iter().map(ACF::from).collect()),
+            ACFRef::Map(map) => ACF::Map(
+                map.into_iter()
+                    .map(|(key, value)| (key.into_owned(), ACF::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Deserializes `T` straight from the input, so `&str` fields and `#[serde(borrow)]` fields
+/// point into `input`. Strings with escapes can only go into owned or `Cow` fields.
+pub fn from_str<'de, T: Deserialize<'de>>(input: &'de str) -> Result<T, Error> {
+    let config = ACFRef::parse(input)
+        .map_err(|offset| de::Error::custom(format!("{offset}: invalid syntax")))?;
+    T::deserialize(config)
+}
+
+impl<'de> de::Deserializer<'de> for ACFRef<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            ACFRef::String(Cow::Borrowed(string)) => visitor.visit_borrowed_str(string),
+            ACFRef::String(Cow::Owned(string)) => visitor.visit_string(string),
+            ACFRef::Integer(integer) => visitor.visit_i64(integer),
+            ACFRef::Float(float) => visitor.visit_f64(float.into_inner()),
+            ACFRef::Boolean(boolean) => visitor.visit_bool(boolean),
+            ACFRef::Secret(secret) => secret.into_exposed().deserialize_any(visitor),
+            ACFRef::Seq(values) => visitor.visit_seq(SeqDeserializer::new(values.into_iter())),
+            ACFRef::Map(map) => visitor.visit_map(MapDeserializer::new(map.into_iter())),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self {
+            ACFRef::String(string) => visitor.visit_enum(string.into_deserializer()),
+            ACFRef::Map(map) if map.len() == 1 => visitor.visit_enum(MapAccessDeserializer::new(
+                MapDeserializer::new(map.into_iter()),
+            )),
+            ACFRef::Secret(secret) => secret
+                .into_exposed()
+                .deserialize_enum(name, variants, visitor),
+            _ => Err(de::Error::invalid_type(
+                de::Unexpected::Other("non enum value"),
+                &visitor,
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for ACFRef<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+#[test]
+fn parse_borrowed_test() {
+    let input = r#"name=demo,quoted="a b",escaped="a\"b",c={"key": 'x', n: !secret 1.5}"#;
+    let config = ACFRef::parse(input).unwrap();
+
+    let ACFRef::Map(map) = &config else {
+        panic!("expected a map")
+    };
+    assert!(matches!(map["name"], ACFRef::String(Cow::Borrowed("demo"))));
+    assert!(matches!(
+        map["quoted"],
+        ACFRef::String(Cow::Borrowed("a b"))
+    ));
+    assert_eq!(
+        ACFRef::String(Cow::Owned("a\"b".to_string())),
+        map["escaped"]
+    );
+
+    let ACFRef::Map(c) = &map["c"] else {
+        panic!("expected a map")
+    };
+    assert!(matches!(c.keys().next(), Some(Cow::Borrowed("key"))));
+    assert!(matches!(c["key"], ACFRef::String(Cow::Borrowed("x"))));
+
+    let owned = crate::tokenized_to_config(input, parser::tokenize_ast(input).unwrap());
+    assert_eq!(owned, config.into_owned());
+    assert_eq!(Err(4), ACFRef::parse("a=1 b"));
+}
+
+#[test]
+fn deserialize_borrowed_test() {
+    #[derive(Debug, serde::Deserialize)]
+    struct Overrides<'a> {
+        region: &'a str,
+        #[serde(borrow)]
+        label: Cow<'a, str>,
+        #[serde(borrow)]
+        zone: Cow<'a, str>,
+        tags: Vec<&'a str>,
+        limit: u32,
+        token: Secret<String>,
+    }
+
+    let input =
+        r#"region=eu-west, label="a\tb", zone=a, tags={x, "y z"}, limit=10, token=!secret abc"#;
+    let out: Overrides = from_str(input).unwrap();
+
+    assert_eq!("eu-west", out.region);
+    assert!(std::ptr::eq(&input[7..14], out.region));
+    assert!(matches!(out.label, Cow::Owned(ref label) if label == "a\tb"));
+    assert!(matches!(out.zone, Cow::Borrowed("a")));
+    assert_eq!(vec!["x", "y z"], out.tags);
+    assert_eq!(10, out.limit);
+    assert_eq!("abc", out.token.expose_secret());
+
+    let escaped: Result<Overrides, _> =
+        from_str(r#"region="e\"u", label=a, zone=a, tags={x}, limit=1, token=!secret b"#);
+    assert!(escaped.is_err());
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+
+use crate::{Kind, Secret, ACF};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConversionError {
+    Type {
+        expected: Kind,
+        found: Kind,
+    },
+    /// The integer does not fit in `target`.
+    OutOfRange {
+        target: &'static str,
+        value: i64,
+    },
+    /// Nothing exists at `pointer`.
+    Missing {
+        pointer: String,
+    },
+}
+
+impl std::fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConversionError::Type { expected, found } => {
+                write!(f, "expected {expected}, found {found}")
+            }
+            ConversionError::OutOfRange { target, value } => {
+                write!(f, "integer {value} does not fit in {target}")
+            }
+            ConversionError::Missing { pointer } => write!(f, "no value at '{pointer}'"),
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+fn type_error(expected: Kind, found: &ACF) -> ConversionError {
+    ConversionError::Type {
+        expected,
+        found: found.kind(),
+    }
+}
+
+impl ACF {
+    /// Looks up `pointer` and converts the value, for example `config.get_as::<u16>("/server/port")`.
+    pub fn get_as<'a, T>(&'a self, pointer: &str) -> Result<T, ConversionError>
+    where
+        T: TryFrom<&'a ACF, Error = ConversionError>,
+    {
+        let value = self
+            .json_pointer(pointer)
+            .ok_or_else(|| ConversionError::Missing {
+                pointer: pointer.to_string(),
+            })?;
+        T::try_from(value)
+    }
+}
+
+impl<'a> TryFrom<&'a ACF> for &'a str {
+    type Error = ConversionError;
+
+    fn try_from(value: &'a ACF) -> Result<Self, Self::Error> {
+        value
+            .as_str()
+            .ok_or_else(|| type_error(Kind::String, value))
+    }
+}
+
+impl TryFrom<&ACF> for String {
+    type Error = ConversionError;
+
+    fn try_from(value: &ACF) -> Result<Self, Self::Error> {
+        <&str>::try_from(value).map(String::from)
+    }
+}
+
+impl TryFrom<&ACF> for bool {
+    type Error = ConversionError;
+
+    fn try_from(value: &ACF) -> Result<Self, Self::Error> {
+        value
+            .as_bool()
+            .ok_or_else(|| type_error(Kind::Boolean, value))
+    }
+}
+
+impl TryFrom<&ACF> for f64 {
+    type Error = ConversionError;
+
+    fn try_from(value: &ACF) -> Result<Self, Self::Error> {
+        value.as_f64().ok_or_else(|| type_error(Kind::Float, value))
+    }
+}
+
+impl TryFrom<&ACF> for f32 {
+    type Error = ConversionError;
+
+    fn try_from(value: &ACF) -> Result<Self, Self::Error> {
+        f64::try_from(value).map(|float| float as f32)
+    }
+}
+
+macro_rules! impl_try_from_integer {
+    ($($target:ty),*) => {
+        $(
+            impl TryFrom<&ACF> for $target {
+                type Error = ConversionError;
+
+                fn try_from(value: &ACF) -> Result<Self, Self::Error> {
+                    let integer = value.as_i64().ok_or_else(|| type_error(Kind::Integer, value))?;
+                    <$target>::try_from(integer).map_err(|_| ConversionError::OutOfRange {
+                        target: stringify!($target),
+                        value: integer,
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl<'a, T> TryFrom<&'a ACF> for Vec<T>
+where
+    T: TryFrom<&'a ACF, Error = ConversionError>,
+{
+    type Error = ConversionError;
+
+    fn try_from(value: &'a ACF) -> Result<Self, Self::Error> {
+        value
+            .as_seq()
+            .ok_or_else(|| type_error(Kind::Seq, value))?
+            .iter()
+            .map(T::try_from)
+            .collect()
+    }
+}
+
+impl<'a, T, S> TryFrom<&'a ACF> for HashMap<String, T, S>
+where
+    T: TryFrom<&'a ACF, Error = ConversionError>,
+    S: BuildHasher + Default,
+{
+    type Error = ConversionError;
+
+    fn try_from(value: &'a ACF) -> Result<Self, Self::Error> {
+        value
+            .as_map()
+            .ok_or_else(|| type_error(Kind::Map, value))?
+            .iter()
+            .map(|(key, value)| Ok((key.clone(), T::try_from(value)?)))
+            .collect()
+    }
+}
+
+/// Only succeeds for values marked as secret, so a secret is never read as a plain value by accident.
+impl<'a, T> TryFrom<&'a ACF> for Secret<T>
+where
+    T: TryFrom<&'a ACF, Error = ConversionError>,
+{
+    type Error = ConversionError;
+
+    fn try_from(value: &'a ACF) -> Result<Self, Self::Error> {
+        let secret = value
+            .as_secret()
+            .ok_or_else(|| type_error(Kind::Secret, value))?;
+        T::try_from(secret.expose_secret().as_ref()).map(Secret::new)
+    }
+}
+
+#[test]
+fn try_from_test() {
+    use crate::{acf_map, acf_seq};
+
+    let config = acf_map! {
+        "server" => acf_map! {
+            "port" => 8080,
+            "big" => 70000,
+            "hosts" => acf_seq!{"a", "b"},
+            "password" => Secret::new("hunter2"),
+        },
+        "limits" => acf_map! {
+            "cpu" => 2,
+            "memory" => 512,
+        },
+    };
+
+    assert_eq!(Ok(8080), config.get_as::<u16>("/server/port"));
+    assert_eq!(
+        Ok(vec!["a".to_string(), "b".to_string()]),
+        config.get_as::<Vec<String>>("/server/hosts")
+    );
+    assert_eq!(
+        Ok(HashMap::from([
+            ("cpu".to_string(), 2u32),
+            ("memory".to_string(), 512u32)
+        ])),
+        config.get_as::<HashMap<String, u32>>("/limits")
+    );
+    assert_eq!(
+        "hunter2",
+        config
+            .get_as::<Secret<&str>>("/server/password")
+            .unwrap()
+            .expose_secret()
+            .to_string()
+    );
+
+    assert_eq!(
+        Err(ConversionError::OutOfRange {
+            target: "u16",
+            value: 70000
+        }),
+        config.get_as::<u16>("/server/big")
+    );
+    assert_eq!(
+        Err(ConversionError::Type {
+            expected: Kind::Integer,
+            found: Kind::Seq
+        }),
+        config.get_as::<i64>("/server/hosts")
+    );
+    assert_eq!(
+        "expected string, found secret",
+        config
+            .get_as::<String>("/server/password")
+            .unwrap_err()
+            .to_string()
+    );
+    assert_eq!(
+        Err(ConversionError::Missing {
+            pointer: "/server/host".to_string()
+        }),
+        config.get_as::<String>("/server/host")
+    );
+}
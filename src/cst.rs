@@ -0,0 +1,510 @@
+//! Lossless syntax tree that keeps every token of the input, including whitespace and
+//! separators, so `cst.to_string() == input`. Values can be replaced without touching the
+//! bytes around them, which makes it possible to edit a config without reformatting it.
+
+use std::fmt;
+
+use snailquote::unescape;
+
+use crate::parser::{self, Range};
+use crate::selector::parse_json_pointer;
+use crate::{acf_map, KeyIndexRef, ACF};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxKind {
+    /// The whole input, a list of `key=value` entries.
+    Document,
+    /// `key=value` in a document or `key: value` in a map.
+    Entry,
+    Map,
+    Seq,
+    /// `!secret` followed by whitespace and a scalar.
+    Secret,
+    Whitespace,
+    Equals,
+    Colon,
+    Comma,
+    OpenBrace,
+    CloseBrace,
+    Key,
+    Scalar,
+    SecretKeyword,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: SyntaxKind,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub kind: SyntaxKind,
+    pub children: Vec<Element>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Element {
+    Node(Node),
+    Token(Token),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CstError {
+    /// The input is not valid, `offset` is the byte position of the error.
+    Parse { offset: usize },
+    /// Nothing exists at `pointer`.
+    Missing { pointer: String },
+    /// The value can't be written at `pointer`, for example a map inside a map.
+    Unsupported { pointer: String },
+}
+
+impl fmt::Display for CstError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CstError::Parse { offset } => write!(f, "{offset}: invalid syntax"),
+            CstError::Missing { pointer } => write!(f, "nothing found at {pointer}"),
+            CstError::Unsupported { pointer } => {
+                write!(f, "value can't be written at {pointer}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CstError {}
+
+/// A parsed document, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cst {
+    pub root: Node,
+}
+
+impl Cst {
+    pub fn parse(input: &str) -> Result<Cst, CstError> {
+        let ast =
+            parser::tokenize_ast(input).map_err(|e| CstError::Parse { offset: e.offset() })?;
+        let parser::ACF::Map(_, entries) = ast else {
+            unreachable!("a document is always a map")
+        };
+
+        let mut builder = Builder { input, position: 0 };
+        let mut children = Vec::new();
+        for (index, (key, value)) in entries.iter().enumerate() {
+            builder.separator(index, &mut children);
+            children.push(builder.entry(key, value, SyntaxKind::Equals));
+        }
+        builder.whitespace(&mut children);
+
+        Ok(Cst {
+            root: Node {
+                kind: SyntaxKind::Document,
+                children,
+            },
+        })
+    }
+
+    /// The value at a JSON pointer. The empty pointer refers to [`Cst::root`], which is not an
+    /// [`Element`], so it returns `None`.
+    pub fn pointer(&self, pointer: &str) -> Option<&Element> {
+        let path: Vec<KeyIndexRef> = parse_json_pointer(pointer)?;
+        let (last, parents) = path.split_last()?;
+
+        let mut node = &self.root;
+        for key in parents {
+            match node.child(key)? {
+                Element::Node(child) => node = child,
+                Element::Token(_) => return None,
+            }
+        }
+        node.child(last)
+    }
+
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Element> {
+        let path: Vec<KeyIndexRef> = parse_json_pointer(pointer)?;
+        let (last, parents) = path.split_last()?;
+
+        let mut node = &mut self.root;
+        for key in parents {
+            match node.child_mut(key)? {
+                Element::Node(child) => node = child,
+                Element::Token(_) => return None,
+            }
+        }
+        node.child_mut(last)
+    }
+
+    /// Replaces the value at `pointer`, every other byte of the document stays the same.
+    pub fn set(&mut self, pointer: &str, value: &ACF) -> Result<(), CstError> {
+        let element = Cst::value(value).ok_or_else(|| CstError::Unsupported {
+            pointer: pointer.to_string(),
+        })?;
+        self.replace(pointer, element)
+    }
+
+    /// Like [`Cst::set`], with `element` written as it is.
+    pub fn replace(&mut self, pointer: &str, element: Element) -> Result<(), CstError> {
+        let target = self.pointer_mut(pointer).ok_or_else(|| CstError::Missing {
+            pointer: pointer.to_string(),
+        })?;
+        let previous = std::mem::replace(target, element);
+
+        if parser::tokenize_ast(&self.to_string()).is_err() {
+            *self.pointer_mut(pointer).expect("just replaced") = previous;
+            return Err(CstError::Unsupported {
+                pointer: pointer.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// The syntax of `value` as written by the serializer, secrets included.
+    pub fn value(value: &ACF) -> Option<Element> {
+        let input = crate::ser::Serializer::new()
+            .reveal_secrets(true)
+            .to_string(&acf_map! {"x" => value.clone()})
+            .ok()?;
+        Cst::parse_value(&input["x=".len()..])
+    }
+
+    /// The syntax of a single value in ACF syntax, such as `42`, `"a b"` or `{1, 2}`.
+    pub fn parse_value(text: &str) -> Option<Element> {
+        let cst = Cst::parse(&format!("x={text}")).ok()?;
+        let mut entries = cst.root.entries();
+        let (Some(entry), None) = (entries.next(), entries.next()) else {
+            return None;
+        };
+        entry.value().cloned()
+    }
+}
+
+impl Node {
+    /// Entries of a document or map.
+    pub fn entries(&self) -> impl Iterator<Item = &Node> {
+        self.child_nodes()
+            .filter(|node| node.kind == SyntaxKind::Entry)
+    }
+
+    /// Items of a sequence.
+    pub fn values(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter(|element| match element {
+            Element::Node(node) => node.kind != SyntaxKind::Entry,
+            Element::Token(token) => token.kind == SyntaxKind::Scalar,
+        })
+    }
+
+    /// The key token of an entry.
+    pub fn key(&self) -> Option<&Token> {
+        self.tokens().find(|token| token.kind == SyntaxKind::Key)
+    }
+
+    /// The value of an entry.
+    pub fn value(&self) -> Option<&Element> {
+        self.children.last()
+    }
+
+    /// The unescaped key of an entry.
+    pub fn key_text(&self) -> Option<String> {
+        self.key()
+            .map(|key| unescape(&key.text).unwrap_or_default())
+    }
+
+    pub fn tokens(&self) -> impl Iterator<Item = &Token> {
+        self.children.iter().filter_map(|element| match element {
+            Element::Token(token) => Some(token),
+            Element::Node(_) => None,
+        })
+    }
+
+    pub fn child_nodes(&self) -> impl Iterator<Item = &Node> {
+        self.children.iter().filter_map(|element| match element {
+            Element::Node(node) => Some(node),
+            Element::Token(_) => None,
+        })
+    }
+
+    fn child_position(&self, key: &KeyIndexRef) -> Option<usize> {
+        match self.kind {
+            SyntaxKind::Document | SyntaxKind::Map => {
+                let key = match key {
+                    KeyIndexRef::String(key) => key.to_string(),
+                    KeyIndexRef::Integer(index) => index.to_string(),
+                };
+                // the last duplicate key wins, as in `tokenized_to_config`
+                self.children.iter().rposition(|element| match element {
+                    Element::Node(node) => {
+                        node.kind == SyntaxKind::Entry && node.key_text().as_ref() == Some(&key)
+                    }
+                    Element::Token(_) => false,
+                })
+            }
+            SyntaxKind::Seq => {
+                let KeyIndexRef::Integer(index) = key else {
+                    return None;
+                };
+                let positions: Vec<_> = self
+                    .children
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, element)| {
+                        !matches!(element, Element::Token(token) if token.kind != SyntaxKind::Scalar)
+                    })
+                    .map(|(position, _)| position)
+                    .collect();
+                let index = if *index < 0 {
+                    positions.len().checked_sub(index.unsigned_abs())?
+                } else {
+                    *index as usize
+                };
+                positions.get(index).copied()
+            }
+            _ => None,
+        }
+    }
+
+    fn child(&self, key: &KeyIndexRef) -> Option<&Element> {
+        let element = &self.children[self.child_position(key)?];
+        match element {
+            Element::Node(entry) if entry.kind == SyntaxKind::Entry => entry.value(),
+            element => Some(element),
+        }
+    }
+
+    fn child_mut(&mut self, key: &KeyIndexRef) -> Option<&mut Element> {
+        let position = self.child_position(key)?;
+        let element = &mut self.children[position];
+        if matches!(element, Element::Node(entry) if entry.kind == SyntaxKind::Entry) {
+            let Element::Node(entry) = element else {
+                unreachable!()
+            };
+            return entry.children.last_mut();
+        }
+        Some(element)
+    }
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Element::Node(node) => node.fmt(f),
+            Element::Token(token) => f.write_str(&token.text),
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.children.iter().try_for_each(|child| child.fmt(f))
+    }
+}
+
+impl fmt::Display for Cst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.root.fmt(f)
+    }
+}
+
+/// Turns the ranges of a parsed AST into tokens, filling the gaps between them. The input
+/// is already validated, so the gaps only contain whitespace and punctuation.
+struct Builder<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl Builder<'_> {
+    fn token(&mut self, kind: SyntaxKind, end: usize) -> Element {
+        let text = self.input[self.position..end].to_string();
+        self.position = end;
+        Element::Token(Token { kind, text })
+    }
+
+    fn whitespace(&mut self, out: &mut Vec<Element>) {
+        let rest = &self.input[self.position..];
+        let length = rest.len() - rest.trim_start_matches([' ', '\t', '\r', '\n']).len();
+        if length > 0 {
+            out.push(self.token(SyntaxKind::Whitespace, self.position + length));
+        }
+    }
+
+    fn punctuation(&mut self, kind: SyntaxKind, out: &mut Vec<Element>) {
+        out.push(self.token(kind, self.position + 1));
+    }
+
+    /// Whitespace before an item, and the comma before every item but the first.
+    fn separator(&mut self, index: usize, out: &mut Vec<Element>) {
+        self.whitespace(out);
+        if index > 0 {
+            self.punctuation(SyntaxKind::Comma, out);
+            self.whitespace(out);
+        }
+    }
+
+    fn entry(&mut self, key: &Range, value: &parser::ACF, separator: SyntaxKind) -> Element {
+        let mut children = vec![self.token(SyntaxKind::Key, key.end)];
+        self.whitespace(&mut children);
+        self.punctuation(separator, &mut children);
+        self.whitespace(&mut children);
+        children.push(self.value(value));
+
+        Element::Node(Node {
+            kind: SyntaxKind::Entry,
+            children,
+        })
+    }
+
+    fn value(&mut self, value: &parser::ACF) -> Element {
+        match value {
+            parser::ACF::Secret(range, inner) => {
+                let mut children = vec![self.token(SyntaxKind::SecretKeyword, range.start + 7)];
+                self.whitespace(&mut children);
+                children.push(self.value(inner));
+                Element::Node(Node {
+                    kind: SyntaxKind::Secret,
+                    children,
+                })
+            }
+            parser::ACF::Map(_, entries) => {
+                let mut children = Vec::new();
+                self.punctuation(SyntaxKind::OpenBrace, &mut children);
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    self.separator(index, &mut children);
+                    children.push(self.entry(key, value, SyntaxKind::Colon));
+                }
+                self.whitespace(&mut children);
+                self.punctuation(SyntaxKind::CloseBrace, &mut children);
+                Element::Node(Node {
+                    kind: SyntaxKind::Map,
+                    children,
+                })
+            }
+            parser::ACF::Seq(_, values) => {
+                let mut children = Vec::new();
+                self.punctuation(SyntaxKind::OpenBrace, &mut children);
+                for (index, value) in values.iter().enumerate() {
+                    self.separator(index, &mut children);
+                    children.push(self.value(value));
+                }
+                self.whitespace(&mut children);
+                self.punctuation(SyntaxKind::CloseBrace, &mut children);
+                Element::Node(Node {
+                    kind: SyntaxKind::Seq,
+                    children,
+                })
+            }
+            scalar => self.token(SyntaxKind::Scalar, scalar.as_range().end),
+        }
+    }
+}
+
+#[test]
+fn cst_lossless_test() {
+    let inputs = [
+        "",
+        "  \n ",
+        "a=1",
+        " a = 1 , b\t=\n\"two, 2\" ",
+        "config1={value: 1, default: 12},config2={DEFAULT: \"testing\"},config3={1,2,3}",
+        "a = { x :  1 ,y:2 } ,\n  b={ 1 , \"}\" , !secret   3 },c=!secret \"p w\"\n",
+    ];
+
+    for input in inputs {
+        let cst = Cst::parse(input).unwrap();
+        assert_eq!(input, cst.to_string());
+    }
+    assert_eq!(Err(CstError::Parse { offset: 4 }), Cst::parse("a=1 b=2"));
+}
+
+#[test]
+fn cst_structure_test() {
+    let cst = Cst::parse("a = 1, b={x: !secret 2}").unwrap();
+
+    let keys: Vec<_> = cst
+        .root
+        .entries()
+        .filter_map(|entry| entry.key_text())
+        .collect();
+    assert_eq!(vec!["a", "b"], keys);
+
+    let Some(Element::Node(secret)) = cst.pointer("/b/x") else {
+        panic!("expected a secret node")
+    };
+    assert_eq!(SyntaxKind::Secret, secret.kind);
+    assert_eq!(
+        vec![
+            SyntaxKind::SecretKeyword,
+            SyntaxKind::Whitespace,
+            SyntaxKind::Scalar
+        ],
+        secret.tokens().map(|token| token.kind).collect::<Vec<_>>()
+    );
+    assert_eq!(0, cst.root.values().count());
+
+    let cst = Cst::parse("s={1, !secret 2, \"3\"}").unwrap();
+    let Some(Element::Node(seq)) = cst.pointer("/s") else {
+        panic!("expected a seq node")
+    };
+    let values: Vec<_> = seq.values().map(|value| value.to_string()).collect();
+    assert_eq!(vec!["1", "!secret 2", "\"3\""], values);
+}
+
+#[test]
+fn cst_set_test() {
+    use crate::acf_seq;
+
+    let input = "name = old ,\n  hosts={ a,  b },  port={ value:1 }";
+    let mut cst = Cst::parse(input).unwrap();
+
+    cst.set("/name", &ACF::from("new value")).unwrap();
+    cst.set("/hosts/1", &ACF::from(3)).unwrap();
+    cst.set("/port/value", &crate::Secret::new(8080).into())
+        .unwrap();
+    assert_eq!(
+        "name = \"new value\" ,\n  hosts={ a,  3 },  port={ value:!secret 8080 }",
+        cst.to_string()
+    );
+
+    cst.set("/name", &acf_seq! {1, 2}).unwrap();
+    assert_eq!(
+        "name = {1, 2} ,\n  hosts={ a,  3 },  port={ value:!secret 8080 }",
+        cst.to_string()
+    );
+
+    assert_eq!(
+        Err(CstError::Unsupported {
+            pointer: "/hosts/0".to_string()
+        }),
+        cst.set("/hosts/0", &acf_seq! {1})
+    );
+    assert_eq!(
+        Err(CstError::Missing {
+            pointer: "/missing".to_string()
+        }),
+        cst.set("/missing", &ACF::from(1))
+    );
+    assert_eq!(
+        "name = {1, 2} ,\n  hosts={ a,  3 },  port={ value:!secret 8080 }",
+        cst.to_string()
+    );
+}
+
+#[test]
+fn cst_duplicate_key_test() {
+    let mut cst = Cst::parse("a=1, b={x: 1, x: 2}, a=3").unwrap();
+
+    assert_eq!("3", cst.pointer("/a").unwrap().to_string());
+    assert_eq!("2", cst.pointer("/b/x").unwrap().to_string());
+
+    cst.set("/a", &ACF::from(4)).unwrap();
+    cst.set("/b/x", &ACF::from(5)).unwrap();
+    assert_eq!("a=1, b={x: 1, x: 5}, a=4", cst.to_string());
+}
+
+#[test]
+fn cst_parse_value_test() {
+    assert_eq!("{1,  2}", Cst::parse_value("{1,  2}").unwrap().to_string());
+    assert_eq!(
+        "!secret \"a b\"",
+        Cst::parse_value("!secret \"a b\"").unwrap().to_string()
+    );
+    assert_eq!(None, Cst::parse_value("1, b=2"));
+    assert_eq!(None, Cst::parse_value(""));
+}
@@ -0,0 +1,127 @@
+use serde::de::value::{BorrowedStrDeserializer, MapDeserializer, SeqDeserializer};
+use serde::de::{self, Deserialize, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+
+use crate::ACF;
+
+pub type Error = de::value::Error;
+
+/// Deserializes `T` from a parsed config. Secret values are handed out as their inner
+/// value, so deserialize them into [`crate::secret::Secret`] to keep them redacted.
+pub fn from_acf<'de, T: Deserialize<'de>>(config: &'de ACF) -> Result<T, Error> {
+    T::deserialize(config)
+}
+
+impl<'de> de::Deserializer<'de> for &'de ACF {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            ACF::String(string) => visitor.visit_borrowed_str(string),
+            ACF::Integer(integer) => visitor.visit_i64(*integer),
+            ACF::Float(float) => visitor.visit_f64(float.into_inner()),
+            ACF::Boolean(boolean) => visitor.visit_bool(*boolean),
+            ACF::Secret(secret) => secret.expose_secret().deserialize_any(visitor),
+            ACF::Seq(values) => visitor.visit_seq(SeqDeserializer::new(values.iter())),
+            ACF::Map(map) => visitor
+                .visit_map(MapDeserializer::new(map.iter().map(|(key, value)| {
+                    (BorrowedStrDeserializer::new(key.as_str()), value)
+                }))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self {
+            ACF::String(string) => visitor.visit_enum(string.as_str().into_deserializer()),
+            ACF::Map(map) if map.len() == 1 => {
+                let (variant, value) = map.first().expect("map has one entry");
+                visitor.visit_enum(de::value::MapAccessDeserializer::new(MapDeserializer::new(
+                    std::iter::once((BorrowedStrDeserializer::new(variant.as_str()), value)),
+                )))
+            }
+            ACF::Secret(secret) => secret
+                .expose_secret()
+                .deserialize_enum(name, variants, visitor),
+            _ => Err(de::Error::invalid_type(
+                de::Unexpected::Other("non enum value"),
+                &visitor,
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for &'de ACF {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+#[test]
+fn deserialize_test() {
+    use crate::secret::Secret;
+    use crate::{acf_map, acf_seq};
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Config<'a> {
+        name: &'a str,
+        port: u16,
+        ratio: f64,
+        hosts: Vec<String>,
+        password: Secret<String>,
+        mode: Mode,
+        extra: Option<bool>,
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    enum Mode {
+        Fast,
+        Slow { factor: i64 },
+    }
+
+    let config = acf_map! {
+        "name" => "demo",
+        "port" => 8080,
+        "ratio" => 0.5,
+        "hosts" => acf_seq!{"a", "b"},
+        "password" => Secret::new("hunter2"),
+        "mode" => acf_map!{"Slow" => acf_map!{"factor" => 3}},
+    };
+
+    let out: Config = from_acf(&config).unwrap();
+
+    assert_eq!("demo", out.name);
+    assert_eq!(8080, out.port);
+    assert_eq!(0.5, out.ratio);
+    assert_eq!(vec!["a", "b"], out.hosts);
+    assert_eq!("hunter2", out.password.expose_secret());
+    assert_eq!("Secret([REDACTED])", format!("{:?}", out.password));
+    assert_eq!(Mode::Slow { factor: 3 }, out.mode);
+    assert_eq!(None, out.extra);
+
+    assert_eq!(Mode::Fast, from_acf(&ACF::from("Fast")).unwrap());
+}
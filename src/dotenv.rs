@@ -0,0 +1,267 @@
+//! Reads `.env` files, so existing `KEY=value` files can be used next to ACF documents.
+//!
+//! Supported are `#` comments, an optional `export` prefix, unquoted values, single quoted
+//! values (taken literally) and double quoted values (with `\n`, `\r`, `\t`, `\"`, `\\` and
+//! `\$` escapes). Quoted values may span several lines. The quotes only belong to the
+//! dotenv syntax, the text inside is typed like an ACF scalar, so `PORT='8080'` is an integer
+//! and `VERSION='"12"'` a string. This is also how [`crate::export`] writes values.
+
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use crate::flatten::parse_scalar;
+use crate::{StringMap, ACF};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DotenvErrorKind {
+    InvalidKey,
+    MissingEquals,
+    UnterminatedQuote,
+    /// Something other than a comment follows a quoted value.
+    TrailingCharacters,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DotenvError {
+    /// 1-based line of the error.
+    pub line: usize,
+    pub kind: DotenvErrorKind,
+}
+
+impl std::fmt::Display for DotenvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self.kind {
+            DotenvErrorKind::InvalidKey => "invalid key",
+            DotenvErrorKind::MissingEquals => "expected '=' after the key",
+            DotenvErrorKind::UnterminatedQuote => "unterminated quoted value",
+            DotenvErrorKind::TrailingCharacters => "unexpected characters after the value",
+        };
+        write!(f, "line {}: {message}", self.line)
+    }
+}
+
+impl std::error::Error for DotenvError {}
+
+/// Parses into a map of typed values. Later keys overwrite earlier ones.
+pub fn parse(input: &str) -> Result<ACF, DotenvError> {
+    Ok(ACF::Map(
+        parse_pairs(input)?
+            .into_iter()
+            .map(|(key, value)| (key, parse_scalar(&value)))
+            .collect::<StringMap<ACF>>(),
+    ))
+}
+
+/// Parses into the raw keys and values, without typing them. Use [`ACF::unflatten_env`] on
+/// the result to turn `DB__PORT` style keys into nested maps.
+pub fn parse_pairs(input: &str) -> Result<Vec<(String, String)>, DotenvError> {
+    let mut reader = Reader {
+        input,
+        chars: input.char_indices().peekable(),
+        line: 1,
+    };
+    let mut pairs = Vec::new();
+
+    loop {
+        reader.skip_while(|ch| ch.is_whitespace());
+        match reader.peek() {
+            None => return Ok(pairs),
+            Some('#') => reader.skip_line(),
+            Some(_) => pairs.push(reader.pair()?),
+        }
+    }
+}
+
+struct Reader<'a> {
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    line: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|(_, ch)| *ch)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let (_, ch) = self.chars.next()?;
+        if ch == '\n' {
+            self.line += 1;
+        }
+        Some(ch)
+    }
+
+    fn position(&mut self) -> usize {
+        self.chars
+            .peek()
+            .map(|(index, _)| *index)
+            .unwrap_or(self.input.len())
+    }
+
+    fn skip_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.position();
+        while self.peek().is_some_and(&predicate) {
+            self.next();
+        }
+        &self.input[start..self.position()]
+    }
+
+    fn skip_line(&mut self) {
+        self.skip_while(|ch| ch != '\n');
+    }
+
+    fn error(&self, kind: DotenvErrorKind) -> DotenvError {
+        DotenvError {
+            line: self.line,
+            kind,
+        }
+    }
+
+    fn pair(&mut self) -> Result<(String, String), DotenvError> {
+        let mut key = self.key()?;
+        if key == "export" && self.peek().is_some_and(|ch| ch == ' ' || ch == '\t') {
+            self.skip_while(|ch| ch == ' ' || ch == '\t');
+            key = self.key()?;
+        }
+
+        self.skip_while(|ch| ch == ' ' || ch == '\t');
+        if self.next() != Some('=') {
+            return Err(self.error(DotenvErrorKind::MissingEquals));
+        }
+        self.skip_while(|ch| ch == ' ' || ch == '\t');
+
+        let value = match self.peek() {
+            Some(quote @ ('\'' | '"')) => {
+                let line = self.line;
+                self.next();
+                let value = self.quoted(quote).ok_or(DotenvError {
+                    line,
+                    kind: DotenvErrorKind::UnterminatedQuote,
+                })?;
+                let rest = self.skip_while(|ch| ch != '\n').trim();
+                if !(rest.is_empty() || rest.starts_with('#')) {
+                    return Err(self.error(DotenvErrorKind::TrailingCharacters));
+                }
+                value
+            }
+            _ => unquoted(self.skip_while(|ch| ch != '\n')),
+        };
+
+        Ok((key.to_string(), value))
+    }
+
+    fn key(&mut self) -> Result<&'a str, DotenvError> {
+        let key = self.skip_while(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '_' | '.' | '-'));
+        if key.is_empty() || key.starts_with(|ch: char| ch.is_ascii_digit()) {
+            return Err(self.error(DotenvErrorKind::InvalidKey));
+        }
+        Ok(key)
+    }
+
+    /// Reads up to and including the closing `quote`.
+    fn quoted(&mut self, quote: char) -> Option<String> {
+        let mut value = String::new();
+        loop {
+            match self.next()? {
+                ch if ch == quote => return Some(value),
+                '\\' if quote == '"' => match self.next()? {
+                    'n' => value.push('\n'),
+                    'r' => value.push('\r'),
+                    't' => value.push('\t'),
+                    ch @ ('"' | '\\' | '$') => value.push(ch),
+                    ch => {
+                        value.push('\\');
+                        value.push(ch);
+                    }
+                },
+                ch => value.push(ch),
+            }
+        }
+    }
+}
+
+/// Strips a trailing ` # comment` and surrounding whitespace.
+fn unquoted(line: &str) -> String {
+    let end = line
+        .char_indices()
+        .find(|(index, ch)| *ch == '#' && line[..*index].ends_with(|ch: char| ch.is_whitespace()))
+        .map(|(index, _)| index)
+        .unwrap_or(line.len());
+    line[..end].trim().to_string()
+}
+
+#[test]
+fn parse_dotenv_test() {
+    use crate::acf_map;
+
+    let input = r#"
+# database settings
+export DB_HOST=localhost # trailing comment
+DB_PORT = 5432
+DB_RATIO=0.5
+DEBUG='false'
+VERSION='"12"'
+EMPTY=
+URL=http://example.com/#anchor
+GREETING="hello\n\"world\"" # comment
+KEY='-----BEGIN KEY-----
+abc
+-----END KEY-----'
+"#;
+
+    assert_eq!(
+        Ok(acf_map! {
+            "DB_HOST" => "localhost",
+            "DB_PORT" => 5432,
+            "DB_RATIO" => 0.5,
+            "DEBUG" => false,
+            "VERSION" => "12",
+            "EMPTY" => "",
+            "URL" => "http://example.com/#anchor",
+            "GREETING" => "hello\n\"world\"",
+            "KEY" => "-----BEGIN KEY-----\nabc\n-----END KEY-----",
+        }),
+        parse(input)
+    );
+}
+
+#[test]
+fn parse_dotenv_export_roundtrip() {
+    use crate::export::{export, Layout, Shell};
+    use crate::{acf_map, acf_seq};
+
+    let config = acf_map! {
+        "db" => acf_map! {"hosts" => acf_seq!{"a", "b c"}, "port" => 5432},
+        "quote" => "it's \"here\"\n$HOME",
+    };
+    let output = export(
+        &config,
+        Shell::Dotenv,
+        &Layout::Flatten {
+            prefix: String::new(),
+        },
+    )
+    .unwrap();
+
+    assert_eq!(
+        Ok(config),
+        ACF::unflatten_env(parse_pairs(&output).unwrap())
+    );
+}
+
+#[test]
+fn parse_dotenv_errors() {
+    let error = |line, kind| Err(DotenvError { line, kind });
+
+    assert_eq!(error(2, DotenvErrorKind::MissingEquals), parse("A=1\nB"));
+    assert_eq!(error(1, DotenvErrorKind::InvalidKey), parse("1A=1"));
+    assert_eq!(error(1, DotenvErrorKind::InvalidKey), parse("=1"));
+    assert_eq!(
+        error(2, DotenvErrorKind::UnterminatedQuote),
+        parse("A=1\nB='x\ny")
+    );
+    assert_eq!(
+        error(1, DotenvErrorKind::TrailingCharacters),
+        parse("A='x' y")
+    );
+}
@@ -0,0 +1,297 @@
+//! Edits a document in place, so every byte outside the edited value stays the same. Existing
+//! values are replaced through the [`Cst`], new ones are spliced into the text after the last
+//! entry of their map or sequence.
+
+use snailquote::unescape;
+
+use crate::cst::{Cst, CstError};
+use crate::parser::{self, Range};
+use crate::selector::{parse_json_pointer, walk_pointer};
+use crate::ser::write_string;
+use crate::KeyIndexRef;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditError {
+    /// The input is not valid, `offset` is the byte position of the error.
+    Parse { offset: usize },
+    /// The pointer is not a valid JSON pointer, or points at the document itself.
+    InvalidPointer,
+    /// The value at `pointer` is a scalar, so it has no children.
+    NotAContainer { pointer: String },
+    /// The sequence at `pointer` has no item `index`, only appending with `-` or the length is
+    /// supported.
+    IndexOutOfBounds { pointer: String, index: String },
+    /// `value` is not a single value, or can't be written at this position.
+    InvalidValue { value: String },
+}
+
+impl std::fmt::Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EditError::Parse { offset } => write!(f, "{offset}: invalid syntax"),
+            EditError::InvalidPointer => f.write_str("invalid pointer"),
+            EditError::NotAContainer { pointer } => {
+                write!(f, "value at {pointer} is not a map or sequence")
+            }
+            EditError::IndexOutOfBounds { pointer, index } => {
+                write!(f, "index {index} is out of bounds at {pointer}")
+            }
+            EditError::InvalidValue { value } => write!(f, "'{value}' is not valid here"),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+/// Sets the value at `pointer` to `value`, given in ACF syntax (`42`, `"a b"`, `{1, 2}`).
+/// Missing keys are appended to their map, `-` appends to a sequence.
+pub fn set(input: &str, pointer: &str, value: &str) -> Result<String, EditError> {
+    let ast = parser::tokenize_ast(input).map_err(|e| EditError::Parse { offset: e.offset() })?;
+    let path: Vec<KeyIndexRef> = parse_json_pointer(pointer).ok_or(EditError::InvalidPointer)?;
+    if path.is_empty() {
+        return Err(EditError::InvalidPointer);
+    }
+
+    let invalid = || EditError::InvalidValue {
+        value: value.to_string(),
+    };
+    let element = Cst::parse_value(value).ok_or_else(invalid)?;
+
+    let mut cst = Cst::parse(input).expect("the input is valid");
+    match cst.replace(pointer, element) {
+        Ok(()) => return Ok(cst.to_string()),
+        Err(CstError::Missing { .. }) => {}
+        Err(_) => return Err(invalid()),
+    }
+
+    let (span, text) = splice(input, &ast, &path, 0, value)?;
+    let out = format!("{}{text}{}", &input[..span.start], &input[span.end..]);
+
+    parser::tokenize_ast(&out).map_err(|_| invalid())?;
+    Ok(out)
+}
+
+/// Finds where to insert a value that doesn't exist yet, and the text to put there.
+fn splice(
+    input: &str,
+    node: &parser::ACF,
+    path: &[KeyIndexRef],
+    depth: usize,
+    value: &str,
+) -> Result<(Range, String), EditError> {
+    let segment = &path[depth];
+    let last = depth + 1 == path.len();
+
+    match node {
+        parser::ACF::Map(_, entries) => {
+            let key = key_text(segment);
+            // the last duplicate key wins, as in `tokenized_to_config`
+            let found = entries
+                .iter()
+                .rev()
+                .find(|(range, _)| unescape(&input[(*range).clone()]).ok().as_ref() == Some(&key));
+
+            match found {
+                Some(_) if last => unreachable!("existing values are replaced through the CST"),
+                Some((_, child)) => splice(input, child, path, depth + 1, value),
+                None => {
+                    let mut entry = String::new();
+                    write_string(&mut entry, &key).expect("writing to a string does not fail");
+
+                    let document = depth == 0;
+                    let (position, separator, assign) = match entries.iter().last() {
+                        Some((last_key, last_value)) => {
+                            let last_span = span(input, last_value);
+                            let separator = match entries.iter().rev().nth(1) {
+                                Some((_, previous)) => {
+                                    &input[span(input, previous).end..last_key.start]
+                                }
+                                None if document => ",",
+                                None => ", ",
+                            };
+                            (
+                                last_span.end,
+                                separator,
+                                &input[last_key.end..last_span.start],
+                            )
+                        }
+                        None => (input.trim_end().len(), "", "="),
+                    };
+
+                    let text = format!(
+                        "{separator}{entry}{assign}{}",
+                        nest(&path[depth + 1..], value)
+                    );
+                    Ok((position..position, text))
+                }
+            }
+        }
+        parser::ACF::Seq(_, values) => {
+            let index = match segment {
+                KeyIndexRef::Integer(index) => Some(*index),
+                KeyIndexRef::String(key) if key.as_str() == "-" => None,
+                KeyIndexRef::String(_) => {
+                    return Err(EditError::IndexOutOfBounds {
+                        pointer: walk_pointer(&path[..depth]),
+                        index: key_text(segment),
+                    })
+                }
+            };
+
+            match index.and_then(|index| values.get(index as usize)) {
+                Some(_) if last => unreachable!("existing values are replaced through the CST"),
+                Some(child) => splice(input, child, path, depth + 1, value),
+                None if index.is_none() || index == Some(values.len() as isize) => {
+                    let last = values.last().expect("sequences are not empty");
+                    let end = span(input, last).end;
+                    let separator = match values.iter().rev().nth(1) {
+                        Some(previous) => {
+                            &input[span(input, previous).end..span(input, last).start]
+                        }
+                        None => ", ",
+                    };
+                    Ok((
+                        end..end,
+                        format!("{separator}{}", nest(&path[depth + 1..], value)),
+                    ))
+                }
+                None => Err(EditError::IndexOutOfBounds {
+                    pointer: walk_pointer(&path[..depth]),
+                    index: key_text(segment),
+                }),
+            }
+        }
+        _ => Err(EditError::NotAContainer {
+            pointer: walk_pointer(&path[..depth]),
+        }),
+    }
+}
+
+/// The text of a value, including the braces of maps and sequences.
+fn span(input: &str, value: &parser::ACF) -> Range {
+    match value {
+        parser::ACF::Map(range, _) | parser::ACF::Seq(range, _) => {
+            let open = input[..range.start]
+                .rfind('{')
+                .expect("composites start with a brace");
+            open..range.end + 1
+        }
+        value => value.as_range().clone(),
+    }
+}
+
+fn key_text(key: &KeyIndexRef) -> String {
+    match key {
+        KeyIndexRef::String(key) => key.to_string(),
+        KeyIndexRef::Integer(index) => index.to_string(),
+    }
+}
+
+/// Wraps `value` in maps for the keys in `path` that don't exist yet.
+fn nest(path: &[KeyIndexRef], value: &str) -> String {
+    match path.split_first() {
+        None => value.to_string(),
+        Some((key, rest)) => {
+            let mut out = String::from("{");
+            write_string(&mut out, &key_text(key)).expect("writing to a string does not fail");
+            out.push_str(": ");
+            out.push_str(&nest(rest, value));
+            out.push('}');
+            out
+        }
+    }
+}
+
+#[test]
+fn edit_replace_test() {
+    let input = " config1 = {value: 1, default: 12} ,\n config2={ \"a\" ,b },secret=!secret x ";
+
+    assert_eq!(
+        Ok(
+            " config1 = {value: 42, default: 12} ,\n config2={ \"a\" ,b },secret=!secret x "
+                .to_string()
+        ),
+        set(input, "/config1/value", "42")
+    );
+    assert_eq!(
+        Ok(
+            " config1 = {value: 1, default: 12} ,\n config2={ \"a\" ,\"b c\" },secret=!secret x "
+                .to_string()
+        ),
+        set(input, "/config2/1", "\"b c\"")
+    );
+    assert_eq!(
+        Ok(" config1 = {1, 2} ,\n config2={ \"a\" ,b },secret=!secret x ".to_string()),
+        set(input, "/config1", "{1, 2}")
+    );
+    assert_eq!(
+        Ok(
+            " config1 = {value: 1, default: 12} ,\n config2={ \"a\" ,b },secret=!secret y "
+                .to_string()
+        ),
+        set(input, "/secret", "!secret y")
+    );
+}
+
+#[test]
+fn edit_append_test() {
+    let input = "a = 1 , b = {x: 1}\n";
+
+    assert_eq!(
+        Ok("a = 1 , b = {x: 1} , c = 2\n".to_string()),
+        set(input, "/c", "2")
+    );
+    assert_eq!(
+        Ok("a = 1 , b = {x: 1, y: 2}\n".to_string()),
+        set(input, "/b/y", "2")
+    );
+    assert_eq!(
+        Ok("a = 1 , b = {x: 1} , \"c d\" = {f: 2}\n".to_string()),
+        set(input, "/c d/f", "2")
+    );
+    // keys are only quoted when they would read back as something else than a string
+    assert_eq!(Ok("a=1,e=2".to_string()), set("a=1", "/e", "2"));
+    assert_eq!(Ok("a=1".to_string()), set("", "/a", "1"));
+    assert_eq!(Ok("a=1,b=2".to_string()), set("a=1", "/b", "2"));
+    assert_eq!(Ok("s={1,2,3}".to_string()), set("s={1,2}", "/s/-", "3"));
+    assert_eq!(Ok("s={1, 2}".to_string()), set("s={1}", "/s/1", "2"));
+}
+
+#[test]
+fn edit_duplicate_key_test() {
+    assert_eq!(Ok("a=1, a=3".to_string()), set("a=1, a=2", "/a", "3"));
+    assert_eq!(
+        Ok("m={x: 1}, m={x: 2, y: 3}".to_string()),
+        set("m={x: 1}, m={x: 2}", "/m/y", "3")
+    );
+}
+
+#[test]
+fn edit_errors_test() {
+    let input = "a=1,s={1,2},m={x: 1}";
+
+    assert_eq!(
+        Err(EditError::NotAContainer {
+            pointer: "/a".to_string()
+        }),
+        set(input, "/a/b", "1")
+    );
+    assert_eq!(
+        Err(EditError::IndexOutOfBounds {
+            pointer: "/s".to_string(),
+            index: "5".to_string()
+        }),
+        set(input, "/s/5", "1")
+    );
+    for value in ["1, b=2", "", "{x: {y: 1}}"] {
+        assert_eq!(
+            Err(EditError::InvalidValue {
+                value: value.to_string()
+            }),
+            set(input, "/m/x", value),
+            "{value}"
+        );
+    }
+    assert_eq!(Err(EditError::InvalidPointer), set(input, "", "1"));
+    assert_eq!(Err(EditError::Parse { offset: 0 }), set("a=", "/a", "1"));
+}
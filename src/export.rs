@@ -0,0 +1,276 @@
+//! Renders a config as shell variables, either as one variable holding the whole document or
+//! as one variable per leaf, named like [`Flattener::env`] keys with every key uppercased.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::flatten::{FlattenError, Flattener};
+use crate::ser::Serializer;
+use crate::ACF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    /// POSIX `sh` and compatible shells, `export NAME='value'`.
+    Sh,
+    /// `set -gx NAME 'value'`.
+    Fish,
+    /// `NAME='value'` lines as read by dotenv loaders.
+    Dotenv,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "sh" | "bash" | "zsh" | "posix" => Ok(Shell::Sh),
+            "fish" => Ok(Shell::Fish),
+            "dotenv" | "env" => Ok(Shell::Dotenv),
+            other => Err(format!("unknown export format '{other}'")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExportError {
+    Flatten(FlattenError),
+    /// Two keys that end up as the same variable name, such as `log-level` and `log_level`.
+    Collision {
+        name: String,
+        first: String,
+        second: String,
+    },
+}
+
+impl std::fmt::Display for ExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportError::Flatten(e) => e.fmt(f),
+            ExportError::Collision {
+                name,
+                first,
+                second,
+            } => write!(f, "'{first}' and '{second}' are both exported as {name}"),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<FlattenError> for ExportError {
+    fn from(e: FlattenError) -> Self {
+        ExportError::Flatten(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Layout {
+    /// One variable with this name, holding the serialized config.
+    Single(String),
+    /// One variable per leaf, every name starts with `prefix`.
+    Flatten { prefix: String },
+}
+
+/// Renders every variable as a line for `shell`. Secrets are written in plain text, since the
+/// output is meant to be consumed by another process. Fails if two keys get the same variable
+/// name.
+pub fn export(config: &ACF, shell: Shell, layout: &Layout) -> Result<String, ExportError> {
+    let variables = match layout {
+        Layout::Single(name) => vec![(
+            variable_name(name),
+            Serializer::new()
+                .reveal_secrets(true)
+                .to_string(config)
+                .map_err(FlattenError::Serialize)?,
+        )],
+        Layout::Flatten { prefix } => {
+            let mut keys = HashMap::new();
+            let mut variables = Vec::new();
+            // export is one way, so mixed case keys are fine as long as the names differ
+            for (key, value) in Flattener::new("__").reveal_secrets(true).flatten(config)? {
+                let key = format!("{prefix}{key}");
+                let name = variable_name(&key.to_uppercase());
+                if let Some(first) = keys.insert(name.clone(), key.clone()) {
+                    return Err(ExportError::Collision {
+                        name,
+                        first,
+                        second: key,
+                    });
+                }
+                variables.push((name, value));
+            }
+            variables
+        }
+    };
+
+    let mut out = String::new();
+    for (name, value) in variables {
+        let value = quote(shell, &value);
+        match shell {
+            Shell::Sh => writeln!(out, "export {name}={value}"),
+            Shell::Fish => writeln!(out, "set -gx {name} {value}"),
+            Shell::Dotenv => writeln!(out, "{name}={value}"),
+        }
+        .expect("writing to a string does not fail");
+    }
+    Ok(out)
+}
+
+/// Quotes `value` so `shell` reads it back unchanged, without any expansion.
+pub fn quote(shell: Shell, value: &str) -> String {
+    match shell {
+        Shell::Sh => format!("'{}'", value.replace('\'', r"'\''")),
+        Shell::Fish => format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'")),
+        Shell::Dotenv if !value.contains(['\'', '\n', '\r']) => format!("'{value}'"),
+        Shell::Dotenv => {
+            let mut out = String::from('"');
+            for ch in value.chars() {
+                match ch {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str(r"\\"),
+                    '\n' => out.push_str(r"\n"),
+                    '\r' => out.push_str(r"\r"),
+                    '$' => out.push_str(r"\$"),
+                    ch => out.push(ch),
+                }
+            }
+            out.push('"');
+            out
+        }
+    }
+}
+
+/// Replaces everything that is not valid in a variable name with `_`.
+pub fn variable_name(name: &str) -> String {
+    let mut out: String = name
+        .chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' })
+        .collect();
+    if !out.starts_with(|ch: char| ch.is_ascii_alphabetic() || ch == '_') {
+        out.insert(0, '_');
+    }
+    out
+}
+
+#[test]
+fn export_single_test() {
+    let input = r#"name="it's",password=!secret "hunter2""#;
+    let config = crate::tokenized_to_config(input, crate::parser::tokenize_ast(input).unwrap());
+    let layout = Layout::Single("APP_CONFIG".to_string());
+
+    assert_eq!(
+        "export APP_CONFIG='name=\"it'\\''s\",password=!secret hunter2'\n",
+        export(&config, Shell::Sh, &layout).unwrap()
+    );
+    assert_eq!(
+        "set -gx APP_CONFIG 'name=\"it\\'s\",password=!secret hunter2'\n",
+        export(&config, Shell::Fish, &layout).unwrap()
+    );
+    assert_eq!(
+        "APP_CONFIG=\"name=\\\"it's\\\",password=!secret hunter2\"\n",
+        export(&config, Shell::Dotenv, &layout).unwrap()
+    );
+}
+
+#[test]
+fn export_flatten_test() {
+    use crate::{acf_map, acf_seq};
+
+    let config = acf_map! {
+        "db" => acf_map! {"hosts" => acf_seq!{"a", "b c"}, "port" => 5432},
+        "log-level" => "info",
+    };
+    let layout = Layout::Flatten {
+        prefix: "APP__".to_string(),
+    };
+
+    assert_eq!(
+        "export APP__DB__HOSTS__0='a'\n\
+         export APP__DB__HOSTS__1='\"b c\"'\n\
+         export APP__DB__PORT='5432'\n\
+         export APP__LOG_LEVEL='info'\n",
+        export(&config, Shell::Sh, &layout).unwrap()
+    );
+}
+
+#[test]
+fn export_flatten_uppercase_test() {
+    use crate::acf_map;
+
+    let config = acf_map! {
+        "config1" => acf_map! {"value" => 1, "default" => 12},
+        "config2" => acf_map! {"DEFAULT" => "testing"},
+    };
+    let layout = Layout::Flatten {
+        prefix: String::new(),
+    };
+
+    assert_eq!(
+        "CONFIG1__VALUE='1'\n\
+         CONFIG1__DEFAULT='12'\n\
+         CONFIG2__DEFAULT='testing'\n",
+        export(&config, Shell::Dotenv, &layout).unwrap()
+    );
+}
+
+#[test]
+fn export_collision_test() {
+    use crate::acf_map;
+
+    let config = acf_map! {"log-level" => "info", "log_level" => "debug"};
+    let layout = Layout::Flatten {
+        prefix: "APP_".to_string(),
+    };
+
+    assert_eq!(
+        Err(ExportError::Collision {
+            name: "APP_LOG_LEVEL".to_string(),
+            first: "APP_log-level".to_string(),
+            second: "APP_log_level".to_string(),
+        }),
+        export(&config, Shell::Sh, &layout)
+    );
+    assert_eq!(
+        "'APP_log-level' and 'APP_log_level' are both exported as APP_LOG_LEVEL",
+        export(&config, Shell::Sh, &layout).unwrap_err().to_string()
+    );
+
+    let config = acf_map! {"db" => acf_map! {"host" => "a", "Host" => "b"}};
+    assert_eq!(
+        Err(ExportError::Collision {
+            name: "APP_DB__HOST".to_string(),
+            first: "APP_db__host".to_string(),
+            second: "APP_db__Host".to_string(),
+        }),
+        export(&config, Shell::Sh, &layout)
+    );
+}
+
+#[test]
+fn quote_test() {
+    // escapes snailquote can produce, plus characters shells treat specially
+    let value = "a'b\"c\\d\ne\tf$g`h!i*j";
+
+    assert_eq!(
+        r#"'a'\''b"c\d"#.to_string() + "\ne\tf$g`h!i*j'",
+        quote(Shell::Sh, value)
+    );
+    assert_eq!(
+        r#"'a\'b"c\\d"#.to_string() + "\ne\tf$g`h!i*j'",
+        quote(Shell::Fish, value)
+    );
+    assert_eq!(
+        r#""a'b\"c\\d\ne"#.to_string() + "\tf\\$g`h!i*j\"",
+        quote(Shell::Dotenv, value)
+    );
+    assert_eq!("''", quote(Shell::Sh, ""));
+    assert_eq!("'$HOME'", quote(Shell::Dotenv, "$HOME"));
+}
+
+#[test]
+fn variable_name_test() {
+    assert_eq!("DB_HOST", variable_name("DB.HOST"));
+    assert_eq!("_0", variable_name("0"));
+    assert_eq!("_", variable_name(""));
+}
@@ -0,0 +1,501 @@
+//! Conversion between nested configs and flat key/value pairs, for systems such as
+//! Kubernetes ConfigMaps, systemd `Environment=` lines or Consul KV.
+//!
+//! Values are written the way the parser reads scalars, so the string `"123"` stays quoted
+//! while the integer `123` does not. Secrets are redacted unless [`Flattener::reveal_secrets`]
+//! is set, then they are written as their plain value. Empty maps and sequences have no leaves
+//! and are dropped.
+//!
+//! [`ACF::unflatten`] turns keys that are exactly `0..n` into a sequence, so a map with such keys
+//! is read back as a sequence. [`ACF::flatten`] and [`ACF::flatten_env`] refuse those maps, as
+//! well as any other key that would not be read back the same, see [`Flattener::round_trip`].
+
+use indexmap::IndexMap;
+
+use crate::selector::walk_pointer;
+use crate::ser::{SerializeError, Serializer};
+use crate::visit::{walk, Visitor};
+use crate::{parser, tokenized_to_config, KeyIndexRef, StringMap, ACF};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FlattenError {
+    Serialize(SerializeError),
+    /// A key that contains the separator, so it would be split into several keys.
+    Separator {
+        pointer: String,
+    },
+    /// A key with uppercase letters, which [`ACF::unflatten_env`] would read back lowercased.
+    Uppercase {
+        pointer: String,
+    },
+    /// A map whose keys are exactly `0..n`, which [`ACF::unflatten`] would read back as a
+    /// sequence.
+    IndexKeys {
+        pointer: String,
+    },
+}
+
+impl std::fmt::Display for FlattenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FlattenError::Serialize(e) => e.fmt(f),
+            FlattenError::Separator { pointer } => {
+                write!(f, "{pointer}: the key can't be split back at the separator")
+            }
+            FlattenError::Uppercase { pointer } => {
+                write!(f, "{pointer}: environment variable keys must be lowercase")
+            }
+            FlattenError::IndexKeys { pointer } => {
+                write!(
+                    f,
+                    "{pointer}: a map with index keys would be read back as a sequence"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for FlattenError {}
+
+impl From<SerializeError> for FlattenError {
+    fn from(e: SerializeError) -> Self {
+        FlattenError::Serialize(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnflattenError {
+    /// The key that is used both as a value and as a parent of other keys.
+    pub key: String,
+}
+
+impl std::fmt::Display for UnflattenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}' is used both as a value and as a parent", self.key)
+    }
+}
+
+impl std::error::Error for UnflattenError {}
+
+/// Flattens into `db.hosts.0=a` style pairs, with sequence indices as path segments.
+#[derive(Debug, Clone)]
+pub struct Flattener {
+    separator: String,
+    env: bool,
+    reveal_secrets: bool,
+    round_trip: bool,
+}
+
+impl Flattener {
+    pub fn new(separator: &str) -> Self {
+        Flattener {
+            separator: separator.to_string(),
+            env: false,
+            reveal_secrets: false,
+            round_trip: false,
+        }
+    }
+
+    /// Environment variable style pairs such as `DB__HOSTS__0=a`, with keys uppercased.
+    pub fn env() -> Self {
+        Flattener {
+            env: true,
+            ..Flattener::new("__")
+        }
+    }
+
+    /// Write the actual value of secrets instead of a placeholder.
+    pub fn reveal_secrets(mut self, reveal: bool) -> Self {
+        self.reveal_secrets = reveal;
+        self
+    }
+
+    /// Refuse configs that [`ACF::unflatten`], or [`ACF::unflatten_env`] for [`Flattener::env`],
+    /// would not read back the same: keys that contain the separator, maps whose keys are
+    /// `0..n` and, for [`Flattener::env`], keys with uppercase letters.
+    pub fn round_trip(mut self, round_trip: bool) -> Self {
+        self.round_trip = round_trip;
+        self
+    }
+
+    /// Fails for values the serializer can't write, such as NaN, and with
+    /// [`Flattener::round_trip`] for keys that would not be read back the same.
+    pub fn flatten(&self, config: &ACF) -> Result<Vec<(String, String)>, FlattenError> {
+        let serializer = Serializer::new().reveal_secrets(self.reveal_secrets);
+
+        if self.round_trip {
+            let mut index_keys = IndexKeys(None);
+            walk(config, &mut index_keys);
+            if let Some(pointer) = index_keys.0 {
+                return Err(FlattenError::IndexKeys { pointer });
+            }
+        }
+
+        config
+            .walk()
+            .map(|(path, value)| {
+                let value = match value {
+                    ACF::Secret(secret) if self.reveal_secrets => secret.expose_secret(),
+                    value => value,
+                };
+                let pointer = || walk_pointer(&path);
+                // the serializer only sees the leaf, so its pointer is relative
+                let value = serializer.to_string(value).map_err(|e| match e {
+                    SerializeError::Nested { .. } => SerializeError::Nested { pointer: pointer() },
+                    SerializeError::NaN { .. } => SerializeError::NaN { pointer: pointer() },
+                })?;
+
+                let segments = segments(&path);
+                let key = segments.join(&self.separator);
+                if self.round_trip {
+                    if !path.is_empty() && !key.split(self.separator.as_str()).eq(segments.iter()) {
+                        return Err(FlattenError::Separator { pointer: pointer() });
+                    }
+                    if self.env && key.chars().any(char::is_uppercase) {
+                        return Err(FlattenError::Uppercase { pointer: pointer() });
+                    }
+                }
+                if !self.env {
+                    return Ok((key, value));
+                }
+                Ok((key.to_uppercase(), value))
+            })
+            .collect()
+    }
+}
+
+impl ACF {
+    /// [`Flattener::flatten`] with secrets redacted, checked to be read back the same by
+    /// [`ACF::unflatten`].
+    pub fn flatten(&self, separator: &str) -> Result<Vec<(String, String)>, FlattenError> {
+        Flattener::new(separator).round_trip(true).flatten(self)
+    }
+
+    /// [`Flattener::env`] with secrets redacted, checked to be read back the same by
+    /// [`ACF::unflatten_env`].
+    pub fn flatten_env(&self) -> Result<Vec<(String, String)>, FlattenError> {
+        Flattener::env().round_trip(true).flatten(self)
+    }
+
+    /// Rebuilds a config from flat pairs. Keys whose children are exactly `0..n` become
+    /// sequences, values that are not a valid scalar are kept as plain strings.
+    pub fn unflatten<I, K, V>(pairs: I, separator: &str) -> Result<ACF, UnflattenError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut root = Node::Branch(IndexMap::new());
+
+        for (key, value) in pairs {
+            let key = key.as_ref();
+            let mut node = &mut root;
+            let mut end = 0;
+
+            for (position, segment) in key.split(separator).enumerate() {
+                node = match node {
+                    Node::Branch(children) => children
+                        .entry(segment.to_string())
+                        .or_insert_with(|| Node::Branch(IndexMap::new())),
+                    Node::Leaf(_) => {
+                        return Err(UnflattenError {
+                            key: key[..end].to_string(),
+                        })
+                    }
+                };
+                end += if position == 0 { 0 } else { separator.len() } + segment.len();
+            }
+
+            match node {
+                Node::Branch(children) if children.is_empty() => {
+                    *node = Node::Leaf(parse_scalar(value.as_ref()));
+                }
+                _ => {
+                    return Err(UnflattenError {
+                        key: key.to_string(),
+                    })
+                }
+            }
+        }
+
+        Ok(root.into_config())
+    }
+
+    /// Reverses [`ACF::flatten_env`], lowercasing every key. Keys flattened with
+    /// [`Flattener::env`] are lowercase, so they are read back unchanged.
+    pub fn unflatten_env<I, K, V>(pairs: I) -> Result<ACF, UnflattenError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        ACF::unflatten(
+            pairs
+                .into_iter()
+                .map(|(key, value)| (key.as_ref().to_lowercase(), value)),
+            "__",
+        )
+    }
+}
+
+fn segments(path: &[KeyIndexRef<'_>]) -> Vec<String> {
+    path.iter()
+        .map(|key| match key {
+            KeyIndexRef::String(key) => key.to_string(),
+            KeyIndexRef::Integer(index) => index.to_string(),
+        })
+        .collect()
+}
+
+/// Whether `keys` are exactly `0..n` in any order, which [`ACF::unflatten`] reads as a sequence.
+fn is_seq<'k>(keys: impl ExactSizeIterator<Item = &'k String>) -> bool {
+    let len = keys.len();
+    len > 0
+        && keys
+            .into_iter()
+            .all(|key| matches!(key.parse::<usize>(), Ok(index) if index < len && index.to_string() == *key))
+}
+
+/// Finds the first map that [`is_seq`].
+struct IndexKeys(Option<String>);
+
+impl<'a> Visitor<'a> for IndexKeys {
+    fn enter_map(&mut self, path: &[KeyIndexRef<'a>], map: &'a StringMap<ACF>) {
+        if self.0.is_none() && is_seq(map.keys()) {
+            self.0 = Some(walk_pointer(path));
+        }
+    }
+}
+
+/// Reads `value` like the parser reads a scalar, falling back to the raw text.
+pub(crate) fn parse_scalar(value: &str) -> ACF {
+    match parser::tokenize_scalar(value) {
+        Ok(tokens) => tokenized_to_config(value, tokens),
+        Err(_) => ACF::from(value),
+    }
+}
+
+enum Node {
+    Leaf(ACF),
+    Branch(IndexMap<String, Node>),
+}
+
+impl Node {
+    fn into_config(self) -> ACF {
+        match self {
+            Node::Leaf(value) => value,
+            Node::Branch(children) => {
+                if is_seq(children.keys()) {
+                    let mut values: Vec<_> = children
+                        .into_iter()
+                        .map(|(key, value)| (key.parse::<usize>().unwrap_or_default(), value))
+                        .collect();
+                    values.sort_by_key(|(index, _)| *index);
+                    ACF::Seq(
+                        values
+                            .into_iter()
+                            .map(|(_, value)| value.into_config())
+                            .collect(),
+                    )
+                } else {
+                    ACF::Map(
+                        children
+                            .into_iter()
+                            .map(|(key, value)| (key, value.into_config()))
+                            .collect(),
+                    )
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn example() -> ACF {
+    use crate::{acf_map, acf_seq};
+
+    acf_map! {
+        "db" => acf_map! {
+            "hosts" => acf_seq!{"a", "b c"},
+            "port" => 5432,
+            "ratio" => 1.0,
+            "version" => "12",
+            "ssl" => false,
+        },
+        "name" => "",
+    }
+}
+
+#[test]
+fn flatten_test() {
+    let pairs = example().flatten(".").unwrap();
+
+    let expected = vec![
+        ("db.hosts.0", "a"),
+        ("db.hosts.1", "\"b c\""),
+        ("db.port", "5432"),
+        ("db.ratio", "1.0"),
+        ("db.version", "\"12\""),
+        ("db.ssl", "false"),
+        ("name", "\"\""),
+    ];
+
+    assert_eq!(
+        expected
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect::<Vec<_>>(),
+        pairs
+    );
+    assert_eq!(Ok(example()), ACF::unflatten(pairs, "."));
+}
+
+#[test]
+fn flatten_env_test() {
+    let pairs = example().flatten_env().unwrap();
+
+    assert_eq!(("DB__HOSTS__0".to_string(), "a".to_string()), pairs[0]);
+    assert_eq!(Ok(example()), ACF::unflatten_env(pairs));
+}
+
+#[test]
+fn flatten_secret_test() {
+    let config = crate::acf_map! {"password" => crate::Secret::new("hunter2")};
+
+    assert_eq!(
+        Ok(vec![(
+            "password".to_string(),
+            "!secret \"[REDACTED]\"".to_string()
+        )]),
+        config.flatten(".")
+    );
+    assert_eq!(
+        Ok(vec![("password".to_string(), "hunter2".to_string())]),
+        Flattener::new(".").reveal_secrets(true).flatten(&config)
+    );
+    assert_eq!(
+        Err(FlattenError::Serialize(SerializeError::NaN {
+            pointer: "/x".to_string()
+        })),
+        crate::acf_map! {"x" => f64::NAN}.flatten(".")
+    );
+}
+
+#[test]
+fn flatten_ambiguous_keys_test() {
+    use crate::acf_map;
+
+    let separator = |pointer: &str| {
+        Err(FlattenError::Separator {
+            pointer: pointer.to_string(),
+        })
+    };
+
+    assert_eq!(separator("/a.b"), acf_map! {"a.b" => 1}.flatten("."));
+    assert_eq!(
+        separator("/a_/b"),
+        acf_map! {"a_" => acf_map! {"b" => 1}}.flatten_env()
+    );
+    // split from the left, so this is read back the same
+    assert_eq!(
+        Ok(vec![("A___B".to_string(), "1".to_string())]),
+        acf_map! {"a" => acf_map! {"_b" => 1}}.flatten_env()
+    );
+    assert_eq!(
+        Ok(vec![("LOG_LEVEL".to_string(), "info".to_string())]),
+        acf_map! {"log_level" => "info"}.flatten_env()
+    );
+    assert_eq!(
+        Err(FlattenError::Uppercase {
+            pointer: "/db/Host".to_string()
+        }),
+        acf_map! {"db" => acf_map! {"Host" => "a"}}.flatten_env()
+    );
+    assert_eq!(
+        Ok(vec![("db.Host".to_string(), "a".to_string())]),
+        acf_map! {"db" => acf_map! {"Host" => "a"}}.flatten(".")
+    );
+    // without a round trip there is nothing to refuse
+    assert_eq!(
+        Ok(vec![("DB__HOST".to_string(), "a".to_string())]),
+        Flattener::env().flatten(&acf_map! {"db" => acf_map! {"Host" => "a"}})
+    );
+    assert_eq!(
+        Ok(vec![("a.b".to_string(), "1".to_string())]),
+        Flattener::new(".").flatten(&acf_map! {"a.b" => 1})
+    );
+}
+
+#[test]
+fn flatten_index_keys_test() {
+    use crate::{acf_map, acf_seq};
+
+    let config = acf_map! {"db" => acf_map! {"1" => "b", "0" => "a"}};
+    let pairs = Flattener::new(".").flatten(&config).unwrap();
+
+    // read back as a sequence, ordered by index
+    assert_eq!(
+        Ok(acf_map! {"db" => acf_seq!{"a", "b"}}),
+        ACF::unflatten(pairs, ".")
+    );
+    assert_eq!(
+        Err(FlattenError::IndexKeys {
+            pointer: "/db".to_string()
+        }),
+        config.flatten(".")
+    );
+    assert_eq!(
+        Err(FlattenError::IndexKeys {
+            pointer: String::new()
+        }),
+        acf_map! {"0" => 1}.flatten_env()
+    );
+
+    for config in [
+        acf_map! {"db" => acf_map! {"0" => "a", "2" => "b"}},
+        acf_map! {"db" => acf_map! {"00" => "a"}},
+        acf_map! {"db" => acf_seq!{acf_map! {"1" => "a"}}},
+    ] {
+        let pairs = config.flatten(".").unwrap();
+        assert_eq!(Ok(config), ACF::unflatten(pairs, "."));
+    }
+}
+
+#[test]
+fn unflatten_test() {
+    use crate::{acf_map, acf_seq};
+
+    let config = ACF::unflatten(
+        [
+            ("list/1", "b"),
+            ("list/0", "a"),
+            ("sparse/0", "x"),
+            ("sparse/2", "y"),
+            ("raw", "not a scalar"),
+        ],
+        "/",
+    )
+    .unwrap();
+
+    assert_eq!(
+        acf_map! {
+            "list" => acf_seq!{"a", "b"},
+            "sparse" => acf_map!{"0" => "x", "2" => "y"},
+            "raw" => "not a scalar",
+        },
+        config
+    );
+
+    assert_eq!(
+        Err(UnflattenError {
+            key: "a.b".to_string()
+        }),
+        ACF::unflatten([("a.b", "1"), ("a.b.c", "2")], ".")
+    );
+    assert_eq!(
+        Err(UnflattenError {
+            key: "a".to_string()
+        }),
+        ACF::unflatten([("a.b", "1"), ("a", "2")], ".")
+    );
+}
@@ -0,0 +1,364 @@
+//! Canonical formatting of ACF text. Items are written as `key=value` and `key: value`,
+//! separated by `, `, strings get as few quotes as possible, and with a width long documents
+//! are split into one item per line. Formatting its own output gives the same text. ACF has no
+//! comments, so there is nothing else to keep.
+//!
+//! Numbers, booleans and unquoted strings are kept as written, so `1_000` and `@file(db.acf)`
+//! stay the same.
+
+use snailquote::unescape;
+
+use crate::parser::{self, Range, SPECIAL_CHARS};
+use crate::ser::{needs_quotes, write_quoted};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Sort the keys of the document and of maps. Duplicate keys keep their order, so the
+    /// last one still wins.
+    pub sort_keys: bool,
+    /// Keep everything on one line if `None`. Otherwise a document longer than this gets one
+    /// item per line, and a map or sequence that doesn't fit gets one value per line.
+    pub width: Option<usize>,
+    /// Spaces before the values of a map or sequence that is split over lines.
+    pub indent: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            sort_keys: false,
+            width: Some(80),
+            indent: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatError {
+    /// Byte position of the syntax error.
+    pub offset: usize,
+}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: invalid syntax", self.offset)
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+pub fn format(input: &str, options: &FormatOptions) -> Result<String, FormatError> {
+    let ast = parser::tokenize_ast(input).map_err(|e| FormatError { offset: e.offset() })?;
+    let parser::ACF::Map(_, items) = ast else {
+        unreachable!("a document is a map")
+    };
+
+    let formatter = Formatter { input, options };
+    let items: Vec<_> = formatter
+        .sorted(items.iter())
+        .map(|(key, value)| (format!("{key}="), value))
+        .collect();
+    let line = items
+        .iter()
+        .map(|(key, value)| format!("{key}{}", formatter.flat(value)))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let Some(width) = options.width else {
+        return Ok(line);
+    };
+    if line.chars().count() <= width {
+        return Ok(line);
+    }
+    Ok(items
+        .iter()
+        .map(|(key, value)| formatter.wrapped(key, value, width))
+        .collect::<Vec<_>>()
+        .join(",\n"))
+}
+
+struct Formatter<'a> {
+    input: &'a str,
+    options: &'a FormatOptions,
+}
+
+impl Formatter<'_> {
+    /// The formatted keys with their values, in the order they are written.
+    fn sorted<'b>(
+        &self,
+        items: impl Iterator<Item = (&'b Range, &'b parser::ACF)>,
+    ) -> impl Iterator<Item = (String, &'b parser::ACF)> {
+        let mut items: Vec<_> = items
+            .map(|(key, value)| (self.text(key, true), value))
+            .collect();
+        if self.options.sort_keys {
+            items.sort_by_cached_key(|(key, _)| unquoted(key));
+        }
+        items.into_iter()
+    }
+
+    /// A key or string with as few quotes as possible. Unquoted words are kept as they are.
+    fn text(&self, range: &Range, is_key: bool) -> String {
+        let text = &self.input[range.clone()];
+        if !text.starts_with('"') {
+            return text.to_string();
+        }
+
+        let value = unescape(text).unwrap_or_default();
+        let needs_quotes = match is_key {
+            // keys are never typed, so `1` or `true` don't need quotes
+            true => {
+                value.is_empty()
+                    || value.contains(|ch: char| {
+                        ch.is_whitespace() || SPECIAL_CHARS.contains(&ch) || ch == '"' || ch == '\''
+                    })
+            }
+            false => needs_quotes(&value),
+        };
+        // `!secret` followed by a line break would start a secret
+        if !needs_quotes && value != "!secret" {
+            return value;
+        }
+
+        let mut out = String::new();
+        write_quoted(&mut out, &value).expect("writing to a string does not fail");
+        out
+    }
+
+    fn scalar(&self, value: &parser::ACF) -> String {
+        match value {
+            parser::ACF::String(range) => self.text(range, false),
+            parser::ACF::Secret(_, value) => format!("!secret {}", self.scalar(value)),
+            value => self.input[value.as_range().clone()].to_string(),
+        }
+    }
+
+    /// The values of a map or sequence, `None` for a scalar.
+    fn values(&self, value: &parser::ACF) -> Option<Vec<String>> {
+        match value {
+            parser::ACF::Map(_, items) => Some(
+                self.sorted(items.iter())
+                    .map(|(key, value)| format!("{key}: {}", self.scalar(value)))
+                    .collect(),
+            ),
+            parser::ACF::Seq(_, values) => {
+                Some(values.iter().map(|value| self.scalar(value)).collect())
+            }
+            _ => None,
+        }
+    }
+
+    fn flat(&self, value: &parser::ACF) -> String {
+        match self.values(value) {
+            Some(values) => format!("{{{}}}", values.join(", ")),
+            None => self.scalar(value),
+        }
+    }
+
+    /// A top level item on its own lines, `key` includes the `=`.
+    fn wrapped(&self, key: &str, value: &parser::ACF, width: usize) -> String {
+        let flat = format!("{key}{}", self.flat(value));
+        // the `,` behind the item
+        if flat.chars().count() < width {
+            return flat;
+        }
+        let Some(values) = self.values(value) else {
+            return flat;
+        };
+
+        let indent = " ".repeat(self.options.indent);
+        let mut out = format!("{key}{{\n");
+        for (index, value) in values.iter().enumerate() {
+            if index > 0 {
+                out.push_str(",\n");
+            }
+            out.push_str(&indent);
+            out.push_str(value);
+        }
+        out.push_str("\n}");
+        out
+    }
+}
+
+fn unquoted(key: &str) -> String {
+    match key.starts_with('"') {
+        true => unescape(key).unwrap_or_default(),
+        false => key.to_string(),
+    }
+}
+
+#[test]
+fn format_test() {
+    let input =
+        "b = 1 ,\n \"a\"={ y :\"two\" , \"x\": !secret  \"1\" }, c={ \"c d\",2.5,\"true\" ,\"\"}";
+    let one_line = FormatOptions {
+        width: None,
+        ..FormatOptions::default()
+    };
+    assert_eq!(
+        Ok("b=1, a={y: two, x: !secret \"1\"}, c={\"c d\", 2.5, \"true\", \"\"}".to_string()),
+        format(input, &one_line)
+    );
+
+    let sorted = FormatOptions {
+        sort_keys: true,
+        ..one_line.clone()
+    };
+    assert_eq!(
+        Ok("a={x: !secret \"1\", y: two}, b=1, c={\"c d\", 2.5, \"true\", \"\"}".to_string()),
+        format(input, &sorted)
+    );
+
+    // keys are not typed, values keep their quotes when they are needed
+    assert_eq!(
+        Ok("1=\"1\", true={false: \"x,y\"}, \"a b\"=\"!secret\", k=@file(x.acf), l=\"@file(x.acf)\"".to_string()),
+        format(
+            "\"1\"=\"1\",\"true\"={\"false\": \"x,y\"},\"a b\"=\"!secret\",k=@file(x.acf),l=\"@file(x.acf)\"",
+            &one_line
+        )
+    );
+    assert_eq!(
+        Ok("a=\"x\\ny\", b=\"it's\", c=1_000, d=x".to_string()),
+        format("a=\"x\\ny\",b=\"it's\",c=1_000,d=\"x\"", &one_line)
+    );
+
+    assert_eq!(Ok(String::new()), format(" \n", &one_line));
+    assert_eq!(Err(FormatError { offset: 4 }), format("a=1 b", &one_line));
+}
+
+#[test]
+fn format_width_test() {
+    let options = FormatOptions {
+        width: Some(20),
+        indent: 2,
+        ..FormatOptions::default()
+    };
+
+    assert_eq!(
+        Ok("a=1, b={x: 1}".to_string()),
+        format("a=1,b={x:1}", &options)
+    );
+    assert_eq!(
+        Ok("name=server,\nhosts={\n  alpha,\n  beta,\n  gamma\n},\nport=8080".to_string()),
+        format(
+            "name=server, hosts={alpha, beta, gamma}, port=8080",
+            &options
+        )
+    );
+    // a line that is too long without anything to split stays as is
+    assert_eq!(
+        Ok("a=1,\nlong=\"a very long string value\"".to_string()),
+        format("a=1,long=\"a very long string value\"", &options)
+    );
+}
+
+#[test]
+fn format_matches_parse_test() {
+    let options = FormatOptions {
+        sort_keys: true,
+        width: Some(30),
+        indent: 4,
+    };
+    for data in crate::handwritten::fragment_inputs(20_000) {
+        let Ok(expected) = parser::parse(&data) else {
+            assert!(format(&data, &options).is_err(), "{data:?}");
+            continue;
+        };
+        let formatted = format(&data, &options).unwrap();
+        assert_eq!(Ok(expected), parser::parse(&formatted), "{data:?}");
+        assert_eq!(
+            Ok(formatted.clone()),
+            format(&formatted, &options),
+            "{data:?}"
+        );
+    }
+}
+
+#[cfg(test)]
+fn quote(text: &str) -> String {
+    let mut out = String::from('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+fn document_strategy() -> impl proptest::strategy::Strategy<Value = String> {
+    use proptest::prelude::*;
+
+    let space = || "[ \t\n]{0,2}";
+    let word = "[a-z_][a-z0-9_@()./-]{0,6}";
+    let primative = prop_oneof![
+        word.prop_map(String::from),
+        "[ -~\t\n]{0,8}".prop_map(|text| quote(&text)),
+        "-?[0-9]{1,5}".prop_map(String::from),
+        "-?[0-9]{1,3}\\.[0-9]{1,3}".prop_map(String::from),
+        prop_oneof![Just("true"), Just("false")].prop_map(String::from),
+    ];
+    let scalar = prop_oneof![
+        3 => primative.clone(),
+        1 => primative.clone().prop_map(|value| format!("!secret {value}")),
+    ];
+    let key = prop_oneof![
+        word.prop_map(String::from),
+        "[ -~]{1,6}".prop_map(|text| quote(&text)),
+    ];
+    let item = |separator| {
+        (key.clone(), space(), space(), scalar.clone()).prop_map(
+            move |(key, before, after, value)| format!("{key}{before}{separator}{after}{value}"),
+        )
+    };
+    let join = |values: Vec<String>, spaces: Vec<String>| {
+        values
+            .iter()
+            .zip(spaces)
+            .map(|(value, space)| format!("{value}{space}"))
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    let composite = prop_oneof![
+        (
+            prop::collection::vec(item(":"), 1..5),
+            prop::collection::vec(space(), 5)
+        ),
+        (
+            prop::collection::vec(scalar.clone(), 1..5),
+            prop::collection::vec(space(), 5)
+        ),
+    ]
+    .prop_map(move |(values, spaces)| format!("{{{}}}", join(values, spaces)));
+    let value = prop_oneof![scalar, composite];
+    let entry = (key, space(), value)
+        .prop_map(|(key, space, value)| format!("{key}{space}={space}{value}"));
+
+    (
+        prop::collection::vec(entry, 0..8),
+        prop::collection::vec(space(), 8),
+    )
+        .prop_map(move |(items, spaces)| join(items, spaces))
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn format_idempotent_test(
+        data in document_strategy(),
+        sort_keys: bool,
+        width in proptest::option::of(0..100usize),
+        indent in 0..8usize,
+    ) {
+        let options = FormatOptions { sort_keys, width, indent };
+        let formatted = format(&data, &options).unwrap();
+
+        proptest::prop_assert_eq!(Ok(formatted.clone()), format(&formatted, &options));
+        proptest::prop_assert_eq!(parser::parse(&data).unwrap(), parser::parse(&formatted).unwrap());
+    }
+}
@@ -1,43 +1,59 @@
-// use std::iter::{Enumerate, Peekable};
-// use std::str::Chars;
+//! Hand-written lexer and parser, an alternative backend to the winnow parser in
+//! [`crate::parser`]. It accepts the same language and builds the same [`parser::ACF`], but
+//! works on bytes and only decodes characters for non-ASCII text in unquoted values.
 
-fn xd((index, ch): &(usize, char)) -> bool {
-    SPECIAL_CHARS.contains(ch)
+use crate::borrowed::unescape_borrowed;
+use crate::parser::{self, parse_float, parse_integer, Range, RangeMap};
+use crate::{KeyIndexRef, ACF};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// A character that can't start a token, like whitespace other than space, tab, `\r`
+    /// and `\n`.
+    UnexpectedCharacter,
+    UnterminatedString,
+    /// A backslash in a quoted string that is not followed by `"`, `\`, `/`, `b`, `f`, `n`,
+    /// `r` or `t`.
+    InvalidEscape,
+    UnexpectedToken,
+    UnexpectedEnd,
 }
 
-// #[derive(Debug)]
-pub struct Tokenizer<'a> {
-    data: &'a str,
-    // chars: Peekable<Enumerate<Chars<'a>>>,
-    chars: Box<dyn Iterator<Item = (usize, char)> + 'a>,
-    last_char: Option<(usize, char)>,
-    start: usize,
-    stop: bool,
-    next_range: Option<Range<usize>>,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    /// Byte position of the error.
+    pub offset: usize,
+    pub kind: ErrorKind,
 }
 
-impl<'a> Tokenizer<'a> {
-    pub fn new(data: &'a str) -> Self {
-        Tokenizer {
-            data,
-            chars: Box::new(data.chars().enumerate()),
-            last_char: None,
-            start: 0,
-            stop: false,
-            next_range: Some(0..1),
-        }
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ErrorKind::UnexpectedCharacter => "unexpected character",
+            ErrorKind::UnterminatedString => "unterminated string",
+            ErrorKind::InvalidEscape => "invalid escape sequence",
+            ErrorKind::UnexpectedToken => "unexpected token",
+            ErrorKind::UnexpectedEnd => "unexpected end of input",
+        })
     }
 }
 
-#[derive(Debug)]
-pub struct Token {
-    kind: TokenKind,
-    range: Range<usize>,
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.offset, self.kind)
+    }
 }
 
-#[derive(Debug)]
+impl std::error::Error for Error {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TokenKind {
+    /// A quoted string, the range includes the quotes.
     String,
+    /// An unquoted value, like `text`, `12` or `true`.
+    Word,
+    /// `!secret` followed by whitespace. Everywhere but in front of a value it is just a word.
+    Secret,
     Assignment,
     BracketOpen,
     BracketClose,
@@ -45,110 +61,624 @@ pub enum TokenKind {
     ItemSeperator,
 }
 
-// impl<'a> Iterator for Tokenizer<'a> {
-//     type Item = Token;
-
-//     fn next(&mut self) -> Option<Self::Item> {
-//         // let range = if let Some(current_range) = self.next_range.take() {
-//         //     current_range
-//         // } else if let Some((current_postion, ch)) = self.chars.next() {
-//         //     let range = self.start..current_postion;
-//         //     self.next_range = Some(current_postion..(current_postion+1));
-//         //     self.start = (current_postion+1);
-//         //     range
-//         // } else {
-//         //     if self.stop {
-//         //         return None;
-//         //     } else {
-//         //         self.stop = true;
-//         //         self.start..self.data.len()
-//         //     }
-//         // };
-//         // let range = if let Some(range) = self.next_range.take() {
-//         //     Some(range)
-//         // } else {
-//         //     let mut range = None;
-//         //     while let Some((index, ch)) = self.chars.next() {
-//         //         if SPECIAL_CHARS.contains(&ch) {
-//         //             if let Some((prev_index, prev_ch)) = self.last_char {
-//         //                 self.next_range = Some(prev_index+1..index);
-//         //                 range = Some(self.start..prev_index+1);
-//         //                 self.last_char = None;
-//         //                 self.start = prev_index;
-//         //                 break;
-//         //             }
-//         //             // range = Some(self.start..index);
-//         //             // self.start = index;
-//         //             // break;
-//         //             self.last_char = Some((index, ch));
-//         //             continue;
-//         //         }
-
-//         //     }
-//         //     range
-//         // };
-
-//         // dbg!(&range);
-
-//         // let range = range?;
-//         // if range.len() > 1 {
-//         //     return Some(Token {
-//         //         kind: TokenKind::String,
-//         //         range,
-//         //     });
-//         // }
-//         // let kind = match &self.data[range.clone()] {
-//         //     "=" => TokenKind::Assignment,
-//         //     "," => TokenKind::ItemSeperator,
-//         //     "{" => TokenKind::BracketOpen,
-//         //     "}" => TokenKind::BracketClose,
-//         //     ":" => TokenKind::KeySeperator,
-//         //     e => panic!("character left: '{}'", e),
-//         // };
-
-//         // Some(Token { kind, range })
-
-//         if self.stop {
-//             return None;
-//         }
-
-//         let mut start = self.start;
-//         let mut end = self.data.len();
-
-//         while let Some((index, ch)) = self.chars.next() {
-//             end = index;
-
-//             if !SPECIAL_CHARS.contains(&ch) {
-//                 continue;
-//             }
-
-//             if start != end {
-//                 self.start = end + 1;
-//                 break;
-//             }
-//         }
-
-//         if end == self.data.len() {
-//             self.stop = true;
-//         }
-
-//         let range = start..end;
-//         if range.len() > 1 {
-//             return Some(Token {
-//                 kind: TokenKind::String,
-//                 range,
-//             });
-//         }
-//         let kind = match &self.data[range.clone()] {
-//             "=" => TokenKind::Assignment,
-//             "," => TokenKind::ItemSeperator,
-//             "{" => TokenKind::BracketOpen,
-//             "}" => TokenKind::BracketClose,
-//             ":" => TokenKind::KeySeperator,
-//             "" => return None,
-//             e => panic!("character left: '{}'", e),
-//         };
-
-//         Some(Token { kind, range })
-//     }
-// }
\ No newline at end of file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub range: Range,
+}
+
+/// Iterator over the tokens of a document. Stops after the first error, unless it is created
+/// with [`Tokenizer::recovering`].
+pub struct Tokenizer<'a> {
+    data: &'a str,
+    position: usize,
+    recover: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(data: &'a str) -> Self {
+        Tokenizer {
+            data,
+            position: 0,
+            recover: false,
+        }
+    }
+
+    /// A tokenizer that goes on behind an error, after the rest of a string with a bad escape
+    /// or after the unexpected character.
+    pub fn recovering(data: &'a str) -> Self {
+        Tokenizer {
+            recover: true,
+            ..Tokenizer::new(data)
+        }
+    }
+
+    /// Where the next token is searched from.
+    pub fn offset(&self) -> usize {
+        self.position
+    }
+
+    fn quoted(&self, start: usize) -> (Range, Option<Error>) {
+        let bytes = self.data.as_bytes();
+        let mut end = start + 1;
+        let mut error = None;
+
+        loop {
+            match bytes.get(end) {
+                Some(b'"') => return (start..end + 1, error),
+                Some(b'\\') => match bytes.get(end + 1) {
+                    Some(b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => end += 2,
+                    Some(_) => {
+                        error.get_or_insert(Error {
+                            offset: end,
+                            kind: ErrorKind::InvalidEscape,
+                        });
+                        end += 1;
+                    }
+                    None => end += 1,
+                },
+                // bytes of multi-byte characters are never '"' or '\'
+                Some(_) => end += 1,
+                None => {
+                    let error = Error {
+                        offset: start,
+                        kind: ErrorKind::UnterminatedString,
+                    };
+                    return (start..end, Some(error));
+                }
+            }
+        }
+    }
+
+    fn word(&self, start: usize) -> (Range, Option<Error>) {
+        let bytes = self.data.as_bytes();
+        let mut end = start;
+
+        while let Some(&byte) = bytes.get(end) {
+            if byte.is_ascii() {
+                if (byte as char).is_whitespace() || parser::SPECIAL_CHARS.contains(&(byte as char))
+                {
+                    break;
+                }
+                end += 1;
+            } else {
+                let ch = self.data[end..]
+                    .chars()
+                    .next()
+                    .expect("non-ASCII bytes start a character here");
+                if ch.is_whitespace() {
+                    break;
+                }
+                end += ch.len_utf8();
+            }
+        }
+
+        if end == start {
+            let ch = self.data[start..].chars().next().expect("not at the end");
+            let error = Error {
+                offset: start,
+                kind: ErrorKind::UnexpectedCharacter,
+            };
+            return (start..start + ch.len_utf8(), Some(error));
+        }
+        (start..end, None)
+    }
+}
+
+fn is_space(byte: Option<&u8>) -> bool {
+    matches!(byte, Some(b' ' | b'\t' | b'\r' | b'\n'))
+}
+
+impl Iterator for Tokenizer<'_> {
+    type Item = Result<Token, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bytes = self.data.as_bytes();
+        while is_space(bytes.get(self.position)) {
+            self.position += 1;
+        }
+
+        let start = self.position;
+        let kind = match bytes.get(start)? {
+            b'=' => TokenKind::Assignment,
+            b',' => TokenKind::ItemSeperator,
+            b'{' => TokenKind::BracketOpen,
+            b'}' => TokenKind::BracketClose,
+            b':' => TokenKind::KeySeperator,
+            b'"' => TokenKind::String,
+            _ => TokenKind::Word,
+        };
+
+        let (range, error) = match kind {
+            TokenKind::String => self.quoted(start),
+            TokenKind::Word => self.word(start),
+            _ => (start..start + 1, None),
+        };
+        if let Some(error) = error {
+            self.position = match self.recover {
+                true => range.end,
+                false => self.data.len(),
+            };
+            return Some(Err(error));
+        }
+        self.position = range.end;
+
+        let kind = if kind == TokenKind::Word
+            && &self.data[range.clone()] == "!secret"
+            && is_space(bytes.get(range.end))
+        {
+            TokenKind::Secret
+        } else {
+            kind
+        };
+        Some(Ok(Token { kind, range }))
+    }
+}
+
+/// Which items of a composite to build.
+#[derive(Clone, Copy)]
+enum Select<'a, 'b> {
+    All,
+    Item(&'a KeyIndexRef<'b>),
+    Nothing,
+}
+
+struct Parser<'a> {
+    data: &'a str,
+    tokens: Tokenizer<'a>,
+    peeked: Option<Token>,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Result<Option<Token>, Error> {
+        match self.peeked.take() {
+            Some(token) => Ok(Some(token)),
+            None => self.tokens.next().transpose(),
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<&Token>, Error> {
+        if self.peeked.is_none() {
+            self.peeked = self.tokens.next().transpose()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    fn expect_next(&mut self) -> Result<Token, Error> {
+        self.next()?.ok_or(Error {
+            offset: self.data.len(),
+            kind: ErrorKind::UnexpectedEnd,
+        })
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<Token, Error> {
+        match self.expect_next()? {
+            token if token.kind == kind => Ok(token),
+            token => Err(unexpected(&token)),
+        }
+    }
+
+    fn document(&mut self) -> Result<parser::ACF, Error> {
+        let mut items = Vec::new();
+        self.items(|parser, key| {
+            items.push((key, parser.value()?));
+            Ok(())
+        })?;
+
+        Ok(parser::ACF::Map(
+            0..self.data.len(),
+            items.into_iter().collect::<RangeMap<_>>(),
+        ))
+    }
+
+    /// Parses the top-level items, `value` has to parse the value after the `=`.
+    fn items(
+        &mut self,
+        mut value: impl FnMut(&mut Self, Range) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        if self.peek()?.is_none() {
+            return Ok(());
+        }
+
+        loop {
+            let token = self.expect_next()?;
+            let key = key(&token)?;
+            self.expect(TokenKind::Assignment)?;
+            value(self, key)?;
+
+            match self.next()? {
+                None => return Ok(()),
+                Some(token) if token.kind == TokenKind::ItemSeperator => {}
+                Some(token) => return Err(unexpected(&token)),
+            }
+        }
+    }
+
+    /// Like [`tokenize_ast`] followed by a pointer lookup, but only the value at `path` is
+    /// built. Everything else is checked and skipped.
+    fn lookup(&mut self, path: &[KeyIndexRef]) -> Result<Option<parser::ACF>, Error> {
+        let Some((first, rest)) = path.split_first() else {
+            return self.document().map(Some);
+        };
+
+        let mut found = None;
+        self.items(|parser, key| {
+            if !matches!(first, KeyIndexRef::String(name) if parser.key_is(&key, name)) {
+                return parser.value_at(None).map(drop);
+            }
+            // like a map, a later duplicate key replaces the earlier value
+            found = match rest {
+                [] => Some(parser.value()?),
+                [segment] => parser.value_at(Some(segment))?,
+                _ => parser.value_at(None).map(|_| None)?,
+            };
+            Ok(())
+        })?;
+        Ok(found)
+    }
+
+    fn key_is(&self, key: &Range, name: &str) -> bool {
+        unescape_borrowed(&self.data[key.clone()]) == name
+    }
+
+    fn value(&mut self) -> Result<parser::ACF, Error> {
+        let token = self.expect_next()?;
+        match token.kind {
+            TokenKind::BracketOpen => Ok(self
+                .composite(Select::All)?
+                .expect("all items are selected")),
+            _ => self.scalar(token),
+        }
+    }
+
+    /// Parses a value but only builds the item at `segment` of a composite, nothing for
+    /// `None`.
+    fn value_at(&mut self, segment: Option<&KeyIndexRef>) -> Result<Option<parser::ACF>, Error> {
+        let token = self.expect_next()?;
+        match (token.kind, segment) {
+            (TokenKind::BracketOpen, Some(segment)) => self.composite(Select::Item(segment)),
+            (TokenKind::BracketOpen, None) => self.composite(Select::Nothing),
+            _ => self.skip_scalar(token).map(|_| None),
+        }
+    }
+
+    /// Parses the items after `{`, up to and including the `}`.
+    fn composite(&mut self, select: Select) -> Result<Option<parser::ACF>, Error> {
+        let mut token = self.expect_next()?;
+        let start = token.range.start;
+        let is_map = key(&token).is_ok()
+            && matches!(
+                self.peek()?,
+                Some(Token {
+                    kind: TokenKind::KeySeperator,
+                    ..
+                })
+            );
+
+        let mut entries = Vec::new();
+        let mut values = Vec::new();
+        let mut found = None;
+        let mut index = 0;
+        let end = loop {
+            if is_map {
+                let key = key(&token)?;
+                self.expect(TokenKind::KeySeperator)?;
+                let value = self.expect_next()?;
+                match select {
+                    Select::All => entries.push((key, self.scalar(value)?)),
+                    Select::Item(KeyIndexRef::String(name)) if self.key_is(&key, name) => {
+                        found = Some(self.scalar(value)?)
+                    }
+                    _ => self.skip_scalar(value)?,
+                }
+            } else {
+                match select {
+                    Select::All => values.push(self.scalar(token)?),
+                    Select::Item(KeyIndexRef::Integer(selected)) if *selected == index => {
+                        found = Some(self.scalar(token)?)
+                    }
+                    _ => self.skip_scalar(token)?,
+                }
+                index += 1;
+            }
+
+            let separator = self.expect_next()?;
+            match separator.kind {
+                TokenKind::ItemSeperator => token = self.expect_next()?,
+                TokenKind::BracketClose => break separator.range.start,
+                _ => return Err(unexpected(&separator)),
+            }
+        };
+
+        match select {
+            Select::All if is_map => Ok(Some(parser::ACF::Map(
+                start..end,
+                entries.into_iter().collect::<RangeMap<_>>(),
+            ))),
+            Select::All => Ok(Some(parser::ACF::Seq(start..end, values))),
+            _ => Ok(found),
+        }
+    }
+
+    /// Checks a scalar without building it.
+    fn skip_scalar(&mut self, token: Token) -> Result<(), Error> {
+        let token = match token.kind {
+            TokenKind::Secret => self.expect_next()?,
+            _ => token,
+        };
+        key(&token).map(drop)
+    }
+
+    fn scalar(&mut self, token: Token) -> Result<parser::ACF, Error> {
+        if token.kind != TokenKind::Secret {
+            return self.primative(token);
+        }
+
+        let value = self.expect_next()?;
+        let value = self.primative(value)?;
+        Ok(parser::ACF::Secret(
+            token.range.start..value.as_range().end,
+            Box::new(value),
+        ))
+    }
+
+    fn primative(&self, token: Token) -> Result<parser::ACF, Error> {
+        primative(self.data, token)
+    }
+}
+
+/// Types a string or word token.
+pub(crate) fn primative(data: &str, token: Token) -> Result<parser::ACF, Error> {
+    match token.kind {
+        TokenKind::String => Ok(parser::ACF::String(token.range)),
+        TokenKind::Word | TokenKind::Secret => {
+            let range = token.range;
+            Ok(match &data[range.clone()] {
+                "true" | "false" => parser::ACF::Boolean(range),
+                text if parse_integer(text).is_ok() => parser::ACF::Integer(range),
+                text if parse_float(text).is_ok() => parser::ACF::Float(range),
+                _ => parser::ACF::String(range),
+            })
+        }
+        _ => Err(unexpected(&token)),
+    }
+}
+
+fn key(token: &Token) -> Result<Range, Error> {
+    match token.kind {
+        TokenKind::String | TokenKind::Word | TokenKind::Secret => Ok(token.range.clone()),
+        _ => Err(unexpected(token)),
+    }
+}
+
+fn unexpected(token: &Token) -> Error {
+    Error {
+        offset: token.range.start,
+        kind: ErrorKind::UnexpectedToken,
+    }
+}
+
+/// Same as [`parser::tokenize_ast`], using the hand-written lexer.
+pub fn tokenize_ast(data: &str) -> Result<parser::ACF, Error> {
+    Parser {
+        data,
+        tokens: Tokenizer::new(data),
+        peeked: None,
+    }
+    .document()
+}
+
+/// The value at `path`, only building that value. See [`crate::lookup`].
+pub(crate) fn lookup(data: &str, path: &[KeyIndexRef]) -> Result<Option<parser::ACF>, Error> {
+    Parser {
+        data,
+        tokens: Tokenizer::new(data),
+        peeked: None,
+    }
+    .lookup(path)
+}
+
+/// Parses a document with the hand-written backend.
+pub fn parse(data: &str) -> Result<ACF, Error> {
+    Ok(crate::tokenized_to_config(data, tokenize_ast(data)?))
+}
+
+#[test]
+fn tokenizer_test() {
+    let data = " a = {\"b, c\": !secret 1 , d:e}\n";
+    let tokens: Vec<_> = Tokenizer::new(data)
+        .map(|token| token.map(|token| (token.kind, &data[token.range])))
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(
+        vec![
+            (TokenKind::Word, "a"),
+            (TokenKind::Assignment, "="),
+            (TokenKind::BracketOpen, "{"),
+            (TokenKind::String, "\"b, c\""),
+            (TokenKind::KeySeperator, ":"),
+            (TokenKind::Secret, "!secret"),
+            (TokenKind::Word, "1"),
+            (TokenKind::ItemSeperator, ","),
+            (TokenKind::Word, "d"),
+            (TokenKind::KeySeperator, ":"),
+            (TokenKind::Word, "e"),
+            (TokenKind::BracketClose, "}"),
+        ],
+        tokens
+    );
+}
+
+#[test]
+fn tokenizer_errors_test() {
+    let first_error = |data| Tokenizer::new(data).find_map(Result::err);
+
+    assert_eq!(
+        Some(Error {
+            offset: 2,
+            kind: ErrorKind::UnterminatedString
+        }),
+        first_error("a=\"x\\\"")
+    );
+    assert_eq!(
+        Some(Error {
+            offset: 4,
+            kind: ErrorKind::InvalidEscape
+        }),
+        first_error("a=\"x\\u0041\"")
+    );
+    assert_eq!(
+        Some(Error {
+            offset: 3,
+            kind: ErrorKind::UnexpectedCharacter
+        }),
+        first_error("a=b\u{a0}")
+    );
+    assert_eq!(None, first_error("\"é\\\"\"=ü"));
+
+    let data = "a=\"\\q\" b\u{a0}c";
+    let tokens: Vec<_> = Tokenizer::new(data)
+        .map(|token| token.map(|token| &data[token.range]).map_err(|e| e.kind))
+        .collect();
+    assert_eq!(
+        vec![Ok("a"), Ok("="), Err(ErrorKind::InvalidEscape)],
+        tokens
+    );
+
+    let tokens: Vec<_> = Tokenizer::recovering(data)
+        .map(|token| token.map(|token| &data[token.range]).map_err(|e| e.kind))
+        .collect();
+    assert_eq!(
+        vec![
+            Ok("a"),
+            Ok("="),
+            Err(ErrorKind::InvalidEscape),
+            Ok("b"),
+            Err(ErrorKind::UnexpectedCharacter),
+            Ok("c")
+        ],
+        tokens
+    );
+}
+
+#[cfg(test)]
+fn assert_same_as_winnow(data: &str) {
+    let expected = parser::tokenize_ast(data).ok();
+    let found = tokenize_ast(data);
+    let error = found.as_ref().err().cloned();
+    assert_eq!(expected, found.ok(), "{data:?}: {error:?}");
+}
+
+#[test]
+fn handwritten_matches_winnow_test() {
+    let inputs = [
+        "",
+        " \n\t",
+        "a=1",
+        "a=1,",
+        ",a=1",
+        "a=1 b=2",
+        "a==1",
+        " a = 1 , b\t=\n\"two, 2\" ",
+        r#"config1={value: 1, default: 1_2, yes: true, number: 1.23},config2={DEFAULT: "testing"}"#,
+        "config1={1,2,3,4,5}",
+        "a={x: !secret 1, \"y z\": 'q'}, b={!secret \"p w\", e, 0.5, -3, 1_000}, c=!secret true",
+        "a={}",
+        "a={1,}",
+        "a={x: 1, 2}",
+        "a={1, x: 2}",
+        "a={x: {y: 1}}",
+        "a={{1}}",
+        "a={x:1}}",
+        "a=!secret",
+        "a=!secret ",
+        "a=!secret x y",
+        "a=!secret !secret x",
+        "a=!secret !secret",
+        "a=!secretive",
+        "a=!secret\"x\"",
+        "!secret = 1",
+        "a={!secret : 1}",
+        "a={!secret x: 1}",
+        "a=\"x",
+        "a=\"x\"y",
+        "a=x\"y\"",
+        "a=\"\\q\"",
+        "a=\"\\u0041\"",
+        "\"quoted key\"=1",
+        "a=é,ü={ß: ø}",
+        "a=b\u{a0}",
+        "a=b\u{a0},c=d",
+        "a=\u{c}",
+        "a=1e3,b=-0.5,c=1_000,d=0x10,e=.5,f=e",
+        "a=true,b=false,c=\"true\"",
+        "dup=1,dup=2",
+        "a",
+        "=1",
+        "a=",
+        "a=}",
+        "a:1",
+    ];
+
+    for input in inputs {
+        assert_same_as_winnow(input);
+    }
+}
+
+/// Random concatenations of syntax fragments, the same on every run.
+#[cfg(test)]
+pub(crate) fn fragment_inputs(count: usize) -> impl Iterator<Item = String> {
+    let fragments = [
+        "k=", "{x: ", ", ", "a", "1", "-2.5", "true", "\"q r\"", "\"\\\"\"", "\"", "\\", "!secret",
+        " ", "\n", "=", ",", "{", "}", ":", "é", "\u{a0}", "'s'",
+    ];
+
+    // xorshift
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut random = move |bound: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as usize % bound
+    };
+
+    (0..count).map(move |_| {
+        let length = random(12);
+        (0..length)
+            .map(|_| fragments[random(fragments.len())])
+            .collect()
+    })
+}
+
+#[test]
+fn handwritten_fragments_match_winnow_test() {
+    let mut accepted = 0;
+    for input in fragment_inputs(20_000) {
+        assert_same_as_winnow(&input);
+        accepted += usize::from(parser::tokenize_ast(&input).is_ok());
+    }
+    assert!(accepted > 1_000, "only {accepted} inputs were valid");
+}
+
+#[test]
+fn handwritten_parse_test() {
+    let data = "a=1,b={x: \"y\\tz\"},c={!secret 1.5}";
+    let expected = crate::tokenized_to_config(data, parser::tokenize_ast(data).unwrap());
+
+    assert_eq!(Ok(expected), parse(data));
+    assert_eq!(
+        Err(Error {
+            offset: 4,
+            kind: ErrorKind::UnexpectedToken
+        }),
+        parse("a=1 b=2")
+    );
+    assert_eq!(
+        Err(Error {
+            offset: 2,
+            kind: ErrorKind::UnexpectedEnd
+        }),
+        parse("a=")
+    );
+}
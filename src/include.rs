@@ -0,0 +1,369 @@
+//! Documents can pull in other files with an unquoted `@file(path)` value, for example
+//! `db=@file(db.acf)`. Paths are resolved relative to the including file and files ending
+//! in `.json` are read as JSON. Quote the value (`"@file(db.acf)"`) to keep it a plain string.
+//!
+//! Unquoted values end at whitespace and at `= , { } :`, so those characters, `)` and `%` are
+//! written as `%` followed by two hex digits of their UTF-8 bytes, like in URLs:
+//! `@file(my%20db.acf)` includes `my db.acf`.
+
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use snailquote::unescape;
+
+use crate::parser::{self, Range};
+use crate::{tokenized_to_config, ACF};
+
+pub trait Loader {
+    fn load(&self, path: &str) -> std::io::Result<String>;
+}
+
+/// Reads includes from the filesystem.
+#[derive(Debug, Default)]
+pub struct FileLoader;
+
+impl Loader for FileLoader {
+    fn load(&self, path: &str) -> std::io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+}
+
+/// Serves includes from memory, mostly useful for tests.
+#[derive(Debug, Default)]
+pub struct MemoryLoader {
+    files: HashMap<String, String>,
+}
+
+impl MemoryLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, path: impl Into<String>, content: impl Into<String>) {
+        self.files.insert(path.into(), content.into());
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for MemoryLoader {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        MemoryLoader {
+            files: iter
+                .into_iter()
+                .map(|(path, content)| (path.into(), content.into()))
+                .collect(),
+        }
+    }
+}
+
+impl Loader for MemoryLoader {
+    fn load(&self, path: &str) -> std::io::Result<String> {
+        self.files.get(path).cloned().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, format!("no file {path}"))
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum IncludeError {
+    /// The loader failed to read `path`.
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    /// `path` is not a valid document, `offset` is the byte position of the error in that file.
+    Parse { path: String, offset: usize },
+    /// `path` is a JSON file that is invalid or contains values ACF can't represent.
+    Json { path: String, message: String },
+    /// The include at `range` in `path` has a `%` that is not followed by two hex digits, or
+    /// escapes that are not UTF-8.
+    Escape { path: String, range: Range },
+    /// The include at `range` in `path` loops back to a file in `chain`.
+    Cycle {
+        path: String,
+        range: Range,
+        chain: Vec<String>,
+    },
+    /// Loading the include at `range` in `path` failed.
+    Include {
+        path: String,
+        range: Range,
+        source: Box<IncludeError>,
+    },
+}
+
+impl std::fmt::Display for IncludeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IncludeError::Io { path, source } => write!(f, "{path}: {source}"),
+            IncludeError::Parse { path, offset } => {
+                write!(f, "{path}:{offset}: invalid syntax")
+            }
+            IncludeError::Json { path, message } => write!(f, "{path}: {message}"),
+            IncludeError::Escape { path, range } => {
+                write!(f, "{path}:{}: invalid escape in include path", range.start)
+            }
+            IncludeError::Cycle { path, range, chain } => write!(
+                f,
+                "{path}:{}: include cycle: {}",
+                range.start,
+                chain.join(" -> ")
+            ),
+            IncludeError::Include {
+                path,
+                range,
+                source,
+            } => write!(f, "{path}:{}: included from here: {source}", range.start),
+        }
+    }
+}
+
+impl std::error::Error for IncludeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IncludeError::Io { source, .. } => Some(source),
+            IncludeError::Include { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Loads `path` and recursively inlines every `@file(...)` value it contains.
+pub fn load<L: Loader + ?Sized>(loader: &L, path: &str) -> Result<ACF, IncludeError> {
+    let mut stack = Vec::new();
+    load_file(loader, &normalize(Path::new(path)), &mut stack)
+}
+
+fn load_file<L: Loader + ?Sized>(
+    loader: &L,
+    path: &str,
+    stack: &mut Vec<String>,
+) -> Result<ACF, IncludeError> {
+    let input = loader.load(path).map_err(|source| IncludeError::Io {
+        path: path.to_string(),
+        source,
+    })?;
+
+    if path.ends_with(".json") {
+        let json_error = |message: String| IncludeError::Json {
+            path: path.to_string(),
+            message,
+        };
+        let value: serde_json::Value =
+            serde_json::from_str(&input).map_err(|e| json_error(e.to_string()))?;
+        return ACF::try_from(value).map_err(|e| json_error(e.to_string()));
+    }
+
+    let tokens = parser::tokenize_ast(&input).map_err(|e| IncludeError::Parse {
+        path: path.to_string(),
+        offset: e.offset(),
+    })?;
+
+    stack.push(path.to_string());
+    let out = resolve(loader, &input, tokens, stack);
+    stack.pop();
+    out
+}
+
+fn resolve<L: Loader + ?Sized>(
+    loader: &L,
+    input: &str,
+    tokens: parser::ACF,
+    stack: &mut Vec<String>,
+) -> Result<ACF, IncludeError> {
+    match tokens {
+        parser::ACF::String(range) => match include_target(&input[range.clone()]) {
+            Some(target) => {
+                let path = stack
+                    .last()
+                    .cloned()
+                    .expect("resolve is called with a file on the stack");
+                let Some(target) = decode(target) else {
+                    return Err(IncludeError::Escape { path, range });
+                };
+                let base = Path::new(&path).parent().unwrap_or(Path::new(""));
+                let target = normalize(&base.join(target));
+
+                if stack.contains(&target) {
+                    let mut chain = stack.clone();
+                    chain.push(target);
+                    return Err(IncludeError::Cycle { path, range, chain });
+                }
+
+                load_file(loader, &target, stack).map_err(|error| match error {
+                    IncludeError::Cycle { .. } => error,
+                    error => IncludeError::Include {
+                        path,
+                        range,
+                        source: Box::new(error),
+                    },
+                })
+            }
+            None => Ok(tokenized_to_config(input, parser::ACF::String(range))),
+        },
+        parser::ACF::Seq(_, values) => values
+            .into_iter()
+            .map(|value| resolve(loader, input, value, stack))
+            .collect::<Result<_, _>>()
+            .map(ACF::Seq),
+        parser::ACF::Map(_, map_values) => map_values
+            .into_iter()
+            .map(|(key, value)| {
+                Ok((
+                    unescape(&input[key]).unwrap_or_default(),
+                    resolve(loader, input, value, stack)?,
+                ))
+            })
+            .collect::<Result<_, _>>()
+            .map(ACF::Map),
+        tokens => Ok(tokenized_to_config(input, tokens)),
+    }
+}
+
+fn include_target(value: &str) -> Option<&str> {
+    value.strip_prefix("@file(")?.strip_suffix(')')
+}
+
+/// Decodes the `%XX` escapes of an include path.
+fn decode(target: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(target.len());
+    let mut rest = target.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+fn normalize(path: &Path) -> String {
+    let mut out = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(out.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                out.pop();
+            }
+            component => out.push(component),
+        }
+    }
+
+    out.to_string_lossy().into_owned()
+}
+
+#[test]
+fn include_test() {
+    use crate::{acf_map, acf_seq};
+
+    let loader = MemoryLoader::from_iter([
+        ("main.acf", "app=demo,db=@file(config/db.acf)"),
+        ("config/db.acf", "hosts={@file(host.json), b},port=5432"),
+        ("config/host.json", r#"{"name": "a", "weight": 2}"#),
+    ]);
+
+    let expected = acf_map! {
+        "app" => "demo",
+        "db" => acf_map! {
+            "hosts" => acf_seq!{acf_map!{"name" => "a", "weight" => 2}, "b"},
+            "port" => 5432,
+        },
+    };
+
+    assert_eq!(expected, load(&loader, "main.acf").unwrap());
+}
+
+#[test]
+fn include_quoted_is_string_test() {
+    use crate::acf_map;
+
+    let loader = MemoryLoader::from_iter([("main.acf", r#"a="@file(other.acf)""#)]);
+
+    assert_eq!(
+        acf_map! {"a" => "@file(other.acf)"},
+        load(&loader, "main.acf").unwrap()
+    );
+}
+
+#[test]
+fn include_escape_test() {
+    use crate::acf_map;
+
+    let loader = MemoryLoader::from_iter([
+        (
+            "main.acf",
+            "a=@file(my%20db%2C%20v%282%29.acf),b=@file(100%25.acf)",
+        ),
+        ("my db, v(2).acf", "x=1"),
+        ("100%.acf", "y=2"),
+    ]);
+
+    assert_eq!(
+        acf_map! {"a" => acf_map! {"x" => 1}, "b" => acf_map! {"y" => 2}},
+        load(&loader, "main.acf").unwrap()
+    );
+
+    for input in ["a=@file(x%2.acf)", "a=@file(x%zz)", "a=@file(%ff)"] {
+        let loader = MemoryLoader::from_iter([("main.acf", input)]);
+        match load(&loader, "main.acf").unwrap_err() {
+            IncludeError::Escape { path, range } => {
+                assert_eq!("main.acf", path);
+                assert_eq!(2..input.len(), range);
+            }
+            error => panic!("unexpected error for {input}: {error:?}"),
+        }
+    }
+}
+
+#[test]
+fn include_cycle_test() {
+    let loader = MemoryLoader::from_iter([
+        ("a.acf", "x=@file(sub/b.acf)"),
+        ("sub/b.acf", "y={1, @file(../a.acf)}"),
+    ]);
+
+    match load(&loader, "a.acf").unwrap_err() {
+        IncludeError::Cycle { path, range, chain } => {
+            assert_eq!("sub/b.acf", path);
+            assert_eq!(6..21, range);
+            assert_eq!(vec!["a.acf", "sub/b.acf", "a.acf"], chain);
+        }
+        error => panic!("unexpected error: {error:?}"),
+    }
+}
+
+#[test]
+fn include_error_span_test() {
+    let loader = MemoryLoader::from_iter([
+        ("main.acf", "a=1,b=@file(broken.acf)"),
+        ("broken.acf", "x={1,"),
+    ]);
+
+    match load(&loader, "main.acf").unwrap_err() {
+        IncludeError::Include {
+            path,
+            range,
+            source,
+        } => {
+            assert_eq!("main.acf", path);
+            assert_eq!(6..23, range);
+            assert!(
+                matches!(*source, IncludeError::Parse { ref path, .. } if path == "broken.acf")
+            );
+        }
+        error => panic!("unexpected error: {error:?}"),
+    }
+
+    assert!(matches!(
+        load(&loader, "missing.acf"),
+        Err(IncludeError::Io { .. })
+    ));
+}
@@ -0,0 +1,180 @@
+//! `config["db"]["hosts"][0]` style access, modelled after `serde_json::Value`.
+//!
+//! ACF has no null value, so indexing panics when the key or index is missing, like indexing a
+//! `HashMap` or `Vec`. Use [`ACF::at`] to get an `Option` instead. Mutable indexing creates
+//! missing map keys as empty maps, but panics when indexing a non-map with a key or when a
+//! sequence index is out of bounds.
+
+use std::fmt::Debug;
+
+use crate::{StringMap, ACF};
+
+/// Types that can index into an [`ACF`], implemented for string keys and integer positions.
+/// Negative positions count from the end of a sequence.
+pub trait Index: Debug + private::Sealed {
+    fn index_into<'a>(&self, config: &'a ACF) -> Option<&'a ACF>;
+
+    fn index_or_insert<'a>(&self, config: &'a mut ACF) -> &'a mut ACF;
+}
+
+impl Index for str {
+    fn index_into<'a>(&self, config: &'a ACF) -> Option<&'a ACF> {
+        config.get(self)
+    }
+
+    fn index_or_insert<'a>(&self, config: &'a mut ACF) -> &'a mut ACF {
+        match config {
+            ACF::Map(map) => map
+                .entry(self.to_string())
+                .or_insert_with(|| ACF::Map(StringMap::default())),
+            config => panic!("cannot index {} with key '{self}'", config.kind()),
+        }
+    }
+}
+
+impl Index for String {
+    fn index_into<'a>(&self, config: &'a ACF) -> Option<&'a ACF> {
+        self.as_str().index_into(config)
+    }
+
+    fn index_or_insert<'a>(&self, config: &'a mut ACF) -> &'a mut ACF {
+        self.as_str().index_or_insert(config)
+    }
+}
+
+impl Index for isize {
+    fn index_into<'a>(&self, config: &'a ACF) -> Option<&'a ACF> {
+        config.get_index(*self)
+    }
+
+    fn index_or_insert<'a>(&self, config: &'a mut ACF) -> &'a mut ACF {
+        let kind = config.kind();
+        config
+            .get_index_mut(*self)
+            .unwrap_or_else(|| panic!("index {self} is out of bounds for {kind}"))
+    }
+}
+
+macro_rules! impl_index_integer {
+    ($($integer:ty),*) => {
+        $(
+            impl Index for $integer {
+                fn index_into<'a>(&self, config: &'a ACF) -> Option<&'a ACF> {
+                    isize::try_from(*self).ok()?.index_into(config)
+                }
+
+                fn index_or_insert<'a>(&self, config: &'a mut ACF) -> &'a mut ACF {
+                    let index = isize::try_from(*self)
+                        .unwrap_or_else(|_| panic!("index {self} is out of bounds"));
+                    index.index_or_insert(config)
+                }
+            }
+        )*
+    };
+}
+
+// `i32` is included so unsuffixed literals such as `config[0]` and `config[-1]` work
+impl_index_integer!(usize, i32);
+
+impl<T: Index + ?Sized> Index for &T {
+    fn index_into<'a>(&self, config: &'a ACF) -> Option<&'a ACF> {
+        (**self).index_into(config)
+    }
+
+    fn index_or_insert<'a>(&self, config: &'a mut ACF) -> &'a mut ACF {
+        (**self).index_or_insert(config)
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for str {}
+    impl Sealed for String {}
+    impl Sealed for isize {}
+    impl Sealed for usize {}
+    impl Sealed for i32 {}
+    impl<T: Sealed + ?Sized> Sealed for &T {}
+}
+
+impl ACF {
+    /// The value at a key or position, `None` if it is missing.
+    pub fn at<I: Index>(&self, index: I) -> Option<&ACF> {
+        index.index_into(self)
+    }
+}
+
+impl<I: Index> std::ops::Index<I> for ACF {
+    type Output = ACF;
+
+    fn index(&self, index: I) -> &ACF {
+        index
+            .index_into(self)
+            .unwrap_or_else(|| panic!("no value at {index:?} in {}", self.kind()))
+    }
+}
+
+impl<I: Index> std::ops::IndexMut<I> for ACF {
+    fn index_mut(&mut self, index: I) -> &mut ACF {
+        index.index_or_insert(self)
+    }
+}
+
+#[test]
+fn index_test() {
+    use crate::{acf_map, acf_seq};
+
+    let config = acf_map! {
+        "db" => acf_map! {
+            "hosts" => acf_seq!{"a", "b", "c"},
+            "port" => 5432,
+        },
+    };
+
+    assert_eq!(ACF::from("a"), config["db"]["hosts"][0]);
+    assert_eq!(ACF::from("c"), config["db"]["hosts"][-1]);
+    assert_eq!(ACF::from("b"), config["db"]["hosts"][1usize]);
+    assert_eq!(ACF::from(5432), config["db"][String::from("port")]);
+
+    assert_eq!(Some(&ACF::from(5432)), config["db"].at("port"));
+    assert_eq!(None, config["db"].at("missing"));
+    assert_eq!(None, config["db"]["hosts"].at(-4));
+    assert_eq!(None, config["db"]["port"].at("key"));
+}
+
+#[test]
+#[should_panic(expected = "no value at \"missing\" in map")]
+fn index_missing_test() {
+    let config = crate::acf_map! {"db" => 1};
+    let _ = &config["missing"];
+}
+
+#[test]
+fn index_mut_test() {
+    use crate::{acf_map, acf_seq};
+
+    let mut config = acf_map! {
+        "db" => acf_map! {
+            "hosts" => acf_seq!{"a", "b"},
+        },
+    };
+
+    config["db"]["hosts"][-1] = ACF::from("z");
+    config["db"]["pool"]["size"] = ACF::from(4);
+
+    assert_eq!(
+        acf_map! {
+            "db" => acf_map! {
+                "hosts" => acf_seq!{"a", "z"},
+                "pool" => acf_map! {"size" => 4},
+            },
+        },
+        config
+    );
+}
+
+#[test]
+#[should_panic(expected = "index 2 is out of bounds for sequence")]
+fn index_mut_out_of_bounds_test() {
+    let mut config = crate::acf_seq! {1, 2};
+    config[2] = ACF::from(3);
+}
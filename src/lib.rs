@@ -9,12 +9,41 @@ pub type Map<K, V> = IndexMap<K, V, RandomState>;
 pub type StringKey = String;
 pub type StringMap<V> = Map<StringKey, V>;
 
+pub mod borrowed;
+pub mod convert;
+pub mod cst;
+pub mod de;
+pub mod dotenv;
+pub mod edit;
+pub mod export;
+pub mod flatten;
+pub mod fmt;
+pub mod handwritten;
+pub mod include;
+pub mod index;
+pub mod lookup;
+#[cfg(feature = "lsp")]
+pub mod lsp;
 pub mod parser;
+pub mod query;
+pub mod schema;
+pub mod secret;
 pub mod selector;
+pub mod ser;
+pub mod stream;
+#[cfg(feature = "toml")]
+pub mod toml;
+pub mod visit;
+#[cfg(feature = "yaml")]
+pub mod yaml;
 
 use parser::{parse_float, parse_integer};
 
-pub use crate::selector::KeyIndexRef;
+pub use crate::borrowed::ACFRef;
+pub use crate::convert::ConversionError;
+pub use crate::lookup::{lookup, LookupError};
+pub use crate::secret::Secret;
+pub use crate::selector::{KeyIndexRef, SelectorError};
 
 #[macro_export]
 macro_rules! acf_map {
@@ -22,7 +51,7 @@ macro_rules! acf_map {
     ($($key:expr => $value:expr),*) => {
         {
             const CAP: usize = <[()]>::len(&[$({ stringify!($key); }),*]);
-            let mut map = crate::StringMap::<ACF>::with_capacity_and_hasher(CAP, ahash::RandomState::new());
+            let mut map = $crate::StringMap::<ACF>::with_capacity_and_hasher(CAP, ahash::RandomState::new());
             $(
                 map.insert($key.into(), ACF::from($value));
             )*
@@ -46,16 +75,42 @@ macro_rules! acf_seq {
     };
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ACF {
     String(KString),
     Integer(i64),
     Float(OrderedFloat<f64>),
     Boolean(bool),
+    Secret(Secret<Box<ACF>>),
     Seq(Vec<ACF>),
     Map(StringMap<ACF>),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Secret,
+    Seq,
+    Map,
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Kind::String => "string",
+            Kind::Integer => "integer",
+            Kind::Float => "float",
+            Kind::Boolean => "boolean",
+            Kind::Secret => "secret",
+            Kind::Seq => "sequence",
+            Kind::Map => "map",
+        })
+    }
+}
+
 impl From<String> for ACF {
     fn from(value: String) -> Self {
         ACF::String(KString::from(value))
@@ -86,6 +141,15 @@ impl From<f64> for ACF {
     }
 }
 
+impl<T> From<Secret<T>> for ACF
+where
+    T: Into<ACF>,
+{
+    fn from(value: Secret<T>) -> Self {
+        ACF::Secret(Secret::new(Box::new(value.into_exposed().into())))
+    }
+}
+
 impl<T> From<Vec<T>> for ACF
 where
     T: Into<ACF>,
@@ -100,7 +164,7 @@ where
     T: Into<ACF> + Clone,
 {
     fn from(value: &[T]) -> Self {
-        ACF::Seq(value.into_iter().map(|x| x.clone().into()).collect())
+        ACF::Seq(value.iter().map(|x| x.clone().into()).collect())
     }
 }
 
@@ -110,7 +174,160 @@ impl From<StringMap<ACF>> for ACF {
     }
 }
 
+/// Returned when converting a JSON `null`, which has no ACF counterpart.
+#[derive(Debug, PartialEq, Eq)]
+pub struct JsonNullError;
+
+impl std::fmt::Display for JsonNullError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("null values are not supported")
+    }
+}
+
+impl std::error::Error for JsonNullError {}
+
+impl TryFrom<serde_json::Value> for ACF {
+    type Error = JsonNullError;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        use serde_json::Value;
+
+        Ok(match value {
+            Value::Null => return Err(JsonNullError),
+            Value::Bool(boolean) => ACF::Boolean(boolean),
+            Value::Number(number) => match number.as_i64() {
+                Some(integer) => ACF::Integer(integer),
+                None => ACF::from(number.as_f64().unwrap_or(f64::NAN)),
+            },
+            Value::String(string) => ACF::from(string),
+            Value::Array(values) => ACF::Seq(
+                values
+                    .into_iter()
+                    .map(ACF::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Value::Object(map) => ACF::Map(
+                map.into_iter()
+                    .map(|(key, value)| Ok((key, ACF::try_from(value)?)))
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+}
+
+/// Secrets are written as their plain value, floats that JSON can't represent become `null`.
+impl From<ACF> for serde_json::Value {
+    fn from(config: ACF) -> Self {
+        use serde_json::Value;
+
+        match config {
+            ACF::String(string) => Value::String(string.to_string()),
+            ACF::Integer(integer) => Value::from(integer),
+            ACF::Float(float) => Value::from(float.into_inner()),
+            ACF::Boolean(boolean) => Value::Bool(boolean),
+            ACF::Secret(secret) => Value::from(*secret.into_exposed()),
+            ACF::Seq(values) => Value::Array(values.into_iter().map(Value::from).collect()),
+            ACF::Map(map) => Value::Object(
+                map.into_iter()
+                    .map(|(key, value)| (key, Value::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
 impl ACF {
+    pub fn kind(&self) -> Kind {
+        match self {
+            ACF::String(_) => Kind::String,
+            ACF::Integer(_) => Kind::Integer,
+            ACF::Float(_) => Kind::Float,
+            ACF::Boolean(_) => Kind::Boolean,
+            ACF::Secret(_) => Kind::Secret,
+            ACF::Seq(_) => Kind::Seq,
+            ACF::Map(_) => Kind::Map,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ACF::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            ACF::Integer(integer) => Some(*integer),
+            _ => None,
+        }
+    }
+
+    /// Integers are widened to floats.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            ACF::Float(float) => Some(float.into_inner()),
+            ACF::Integer(integer) => Some(*integer as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ACF::Boolean(boolean) => Some(*boolean),
+            _ => None,
+        }
+    }
+
+    pub fn as_secret(&self) -> Option<&Secret<Box<ACF>>> {
+        match self {
+            ACF::Secret(secret) => Some(secret),
+            _ => None,
+        }
+    }
+
+    pub fn as_seq(&self) -> Option<&[ACF]> {
+        match self {
+            ACF::Seq(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&StringMap<ACF>> {
+        match self {
+            ACF::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    pub fn is_string(&self) -> bool {
+        matches!(self, ACF::String(_))
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self, ACF::Integer(_))
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, ACF::Float(_))
+    }
+
+    pub fn is_boolean(&self) -> bool {
+        matches!(self, ACF::Boolean(_))
+    }
+
+    pub fn is_secret(&self) -> bool {
+        matches!(self, ACF::Secret(_))
+    }
+
+    pub fn is_seq(&self) -> bool {
+        matches!(self, ACF::Seq(_))
+    }
+
+    pub fn is_map(&self) -> bool {
+        matches!(self, ACF::Map(_))
+    }
+
     pub fn get(&self, key: &str) -> Option<&Self> {
         match self {
             ACF::Map(map) => map.get(key),
@@ -134,6 +351,27 @@ impl ACF {
         }
     }
 
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Self> {
+        match self {
+            ACF::Map(map) => map.get_mut(key),
+            _ => None,
+        }
+    }
+
+    pub fn get_index_mut(&mut self, index: isize) -> Option<&mut Self> {
+        match self {
+            ACF::Seq(vector) => {
+                let position = if index >= 0 {
+                    index as usize
+                } else {
+                    vector.len().checked_sub(index.checked_abs()? as usize)?
+                };
+                vector.get_mut(position)
+            }
+            _ => None,
+        }
+    }
+
     pub fn selector(&self, selector: &[KeyIndexRef<'_>]) -> Option<&Self> {
         selector::selector(self, selector)
     }
@@ -154,6 +392,39 @@ impl ACF {
 
         Some(config_pointer)
     }
+
+    pub fn try_selector(&self, selector: &[KeyIndexRef<'_>]) -> Result<&Self, SelectorError> {
+        selector::try_selector(self, selector)
+    }
+
+    pub fn try_json_pointer(&self, pointer: &str) -> Result<&Self, SelectorError> {
+        let path = selector::parse_json_pointer::<SmallVec<[_; 8]>>(pointer).ok_or_else(|| {
+            SelectorError {
+                segment: 0,
+                at: String::new(),
+                kind: selector::SelectorErrorKind::InvalidPointer,
+            }
+        })?;
+
+        selector::try_selector(self, &path)
+    }
+
+    /// Merges `other` on top of `self`. Maps are merged key by key, every other value is replaced.
+    pub fn merge(&mut self, other: ACF) {
+        match (self, other) {
+            (ACF::Map(map), ACF::Map(other)) => {
+                for (key, value) in other {
+                    match map.get_mut(&key) {
+                        Some(existing) => existing.merge(value),
+                        None => {
+                            map.insert(key, value);
+                        }
+                    }
+                }
+            }
+            (this, other) => *this = other,
+        }
+    }
 }
 
 pub fn tokenized_to_config(input: &str, tokens: parser::ACF) -> ACF {
@@ -166,7 +437,10 @@ pub fn tokenized_to_config(input: &str, tokens: parser::ACF) -> ACF {
             parse_float(&input[range]).expect("tokenizer checked this"),
         )),
         parser::ACF::String(range) => {
-            ACF::String(unescape(&input[range]).unwrap_or(String::new()).into())
+            ACF::String(unescape(&input[range]).unwrap_or_default().into())
+        }
+        parser::ACF::Secret(_, value) => {
+            ACF::Secret(Secret::new(Box::new(tokenized_to_config(input, *value))))
         }
         parser::ACF::Seq(_, values) => ACF::Seq(
             values
@@ -179,7 +453,7 @@ pub fn tokenized_to_config(input: &str, tokens: parser::ACF) -> ACF {
                 .into_iter()
                 .map(|(key, value)| {
                     (
-                        unescape(&input[key]).unwrap_or(String::new()),
+                        unescape(&input[key]).unwrap_or_default(),
                         tokenized_to_config(input, value),
                     )
                 })
@@ -198,12 +472,12 @@ fn to_boolean(input: &str) -> bool {
 
 #[test]
 fn parse_config() {
-    let mut data = r#"
+    let data = r#"
     config1={value: 1, default: 12, yes: true},
     config2={DEFAULT: "testing", extra: "extra \"quotes\""},
     config3={false, 123, 1.23}
     "#;
-    let tokens = parser::tokenize_ast(&mut data).unwrap();
+    let tokens = parser::tokenize_ast(data).unwrap();
 
     let out = tokenized_to_config(data, tokens);
 
@@ -223,6 +497,86 @@ fn parse_config() {
     assert_eq!(out, expected);
 }
 
+#[test]
+fn parse_config_secret() {
+    let data = r#"user=admin, password=!secret "hunter2", keys={1, !secret 2}"#;
+    let tokens = parser::tokenize_ast(data).unwrap();
+
+    let out = tokenized_to_config(data, tokens);
+
+    let expected = acf_map! {
+        "user" => "admin",
+        "password" => Secret::new("hunter2"),
+        "keys" => acf_seq!{1, Secret::new(2)},
+    };
+
+    assert_eq!(out, expected);
+    assert!(!format!("{out:?}").contains("hunter2"));
+}
+
+#[test]
+fn acf_from_json() {
+    let json = serde_json::json!({
+        "config1": {"value": 1, "yes": true},
+        "config2": ["testing", 1.5],
+    });
+
+    let expected = acf_map! {
+        "config1" => acf_map! {
+            "value" => 1,
+            "yes" => true,
+        },
+        "config2" => acf_seq!{"testing", 1.5}
+    };
+
+    assert_eq!(Ok(expected.clone()), ACF::try_from(json.clone()));
+    assert_eq!(json, serde_json::Value::from(expected));
+    assert_eq!(
+        Err(JsonNullError),
+        ACF::try_from(serde_json::json!({"a": [null]}))
+    );
+}
+
+#[test]
+fn accessor_test() {
+    let config = acf_map! {
+        "name" => "demo",
+        "port" => 8080,
+        "ratio" => 0.5,
+        "hosts" => acf_seq!{"a", "b"},
+    };
+
+    assert_eq!(Some("demo"), config.get("name").and_then(ACF::as_str));
+    assert_eq!(Some(8080), config.get("port").and_then(ACF::as_i64));
+    assert_eq!(Some(8080.0), config.get("port").and_then(ACF::as_f64));
+    assert_eq!(None, config.get("ratio").and_then(ACF::as_i64));
+    assert_eq!(2, config.get("hosts").and_then(ACF::as_seq).unwrap().len());
+    assert!(config.is_map());
+    assert!(!config.is_seq());
+}
+
+#[test]
+fn acf_try_json_pointer() {
+    let config = acf_map! {
+        "config1" => acf_map! {
+            "value" => 1,
+        },
+    };
+
+    assert_eq!(Ok(&ACF::from(1)), config.try_json_pointer("/config1/value"));
+    assert_eq!(
+        "no key 'valeu' at /config1, did you mean 'value'?",
+        config
+            .try_json_pointer("/config1/valeu")
+            .unwrap_err()
+            .to_string()
+    );
+    assert_eq!(
+        "invalid JSON pointer",
+        config.try_json_pointer("config1").unwrap_err().to_string()
+    );
+}
+
 #[test]
 fn acf_key_index_bounds() {
     let data = acf_seq! {1, 2};
@@ -235,6 +589,16 @@ fn acf_key_index_bounds() {
     assert!(data.get_index(-2).is_some());
 }
 
+#[test]
+fn acf_key_index_mut_bounds() {
+    let mut data = acf_seq! {1, 2};
+
+    assert!(data.get_index(-3).is_none());
+    assert!(data.get_index_mut(isize::MIN).is_none());
+    assert!(data.get_index_mut(2).is_none());
+    assert_eq!(Some(&mut ACF::from(1)), data.get_index_mut(-2));
+}
+
 #[test]
 fn acf_json_pointer() {
     let config = acf_map! {
@@ -268,3 +632,24 @@ fn acf_json_pointer() {
     );
     assert_eq!(&ACF::from(1.23), config.json_pointer("/config3/2").unwrap());
 }
+
+#[test]
+fn acf_merge() {
+    let mut config = acf_map! {
+        "db" => acf_map! {"host" => "localhost", "port" => 5432},
+        "hosts" => acf_seq!{"a", "b"},
+    };
+    config.merge(acf_map! {
+        "db" => acf_map! {"port" => 6543},
+        "hosts" => acf_seq!{"c"},
+        "debug" => true,
+    });
+
+    let expected = acf_map! {
+        "db" => acf_map! {"host" => "localhost", "port" => 6543},
+        "hosts" => acf_seq!{"c"},
+        "debug" => true,
+    };
+
+    assert_eq!(expected, config);
+}
@@ -0,0 +1,95 @@
+//! Gets a single value out of a document without building the rest of it.
+
+use smallvec::SmallVec;
+
+use crate::selector::parse_json_pointer;
+use crate::{handwritten, tokenized_to_config, ACF};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LookupError {
+    /// The input is not valid, `offset` is the byte position of the error.
+    Parse {
+        offset: usize,
+    },
+    InvalidPointer,
+}
+
+impl std::fmt::Display for LookupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LookupError::Parse { offset } => write!(f, "{offset}: invalid syntax"),
+            LookupError::InvalidPointer => f.write_str("invalid pointer"),
+        }
+    }
+}
+
+impl std::error::Error for LookupError {}
+
+/// The value at the JSON `pointer`, same as [`ACF::json_pointer`] on the parsed document.
+/// The whole input is still checked, but values under other keys are skipped instead of
+/// built, and only the target is unescaped and typed.
+pub fn lookup(input: &str, pointer: &str) -> Result<Option<ACF>, LookupError> {
+    let path: SmallVec<[_; 8]> = parse_json_pointer(pointer).ok_or(LookupError::InvalidPointer)?;
+    let found =
+        handwritten::lookup(input, &path).map_err(|e| LookupError::Parse { offset: e.offset })?;
+
+    Ok(found.map(|tokens| tokenized_to_config(input, tokens)))
+}
+
+#[test]
+fn lookup_test() {
+    let input = r#"feature={x: true, "y z": !secret 2, x: false},list={a, "b", 3},name="n\"1",dup=1,dup={1}"#;
+    assert_eq!(Ok(Some(ACF::Boolean(false))), lookup(input, "/feature/x"));
+    assert_eq!(Ok(Some(ACF::from("b"))), lookup(input, "/list/1"));
+    assert_eq!(Ok(Some(ACF::from("n\"1"))), lookup(input, "/name"));
+    assert_eq!(
+        Ok(Some(ACF::Seq(vec![ACF::Integer(1)]))),
+        lookup(input, "/dup")
+    );
+
+    assert_eq!(Ok(None), lookup(input, "/feature/missing"));
+    assert_eq!(Ok(None), lookup(input, "/list/3"));
+    assert_eq!(Ok(None), lookup(input, "/name/0"));
+    assert_eq!(Err(LookupError::InvalidPointer), lookup(input, "feature"));
+    assert!(matches!(
+        lookup("feature=1, b", "/feature"),
+        Err(LookupError::Parse { .. })
+    ));
+}
+
+#[test]
+fn lookup_matches_json_pointer_test() {
+    let input = r#"a=1, "b c"={x: 1, y: "two", "~/": 3, 0: zero}, s={1, !secret 2, "3"}, t=!secret x, a=2, e={1}, e={q: 1}"#;
+    let config = tokenized_to_config(input, crate::parser::tokenize_ast(input).unwrap());
+
+    for pointer in [
+        "",
+        "/a",
+        "/b c",
+        "/b c/x",
+        "/b c/y",
+        "/b c/~0~1",
+        "/b c/0",
+        "/b c/x/0",
+        "/s",
+        "/s/0",
+        "/s/1",
+        "/s/2",
+        "/s/3",
+        "/s/-1",
+        "/s/x",
+        "/t",
+        "/t/0",
+        "/e",
+        "/e/0",
+        "/e/q",
+        "/missing",
+        "/0",
+    ] {
+        assert_eq!(
+            Ok(config.json_pointer(pointer).cloned()),
+            lookup(input, pointer),
+            "{pointer}"
+        );
+    }
+}
@@ -0,0 +1,770 @@
+//! Language server for ACF documents, speaking LSP over an [`lsp_server::Connection`]. The
+//! `acf-lsp` binary runs it over stdio.
+//!
+//! It reports the diagnostics of [`crate::parser::tokenize_recovering`], shows the JSON
+//! pointer of the value under the cursor on hover, formats documents with
+//! [`crate::fmt::format`], jumps to the key a `${...}` reference in a string points to,
+//! and completes keys from a schema. References are dot paths (`${db.hosts[0]}`) or JSON
+//! pointers (`${/db/hosts/0}`). The schema is set with the `schema` initialization option,
+//! either the path of an ACF or JSON schema file or an inline JSON schema. A schema that
+//! can't be loaded fails the `initialize` request.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error::Error;
+
+use kstring::KStringCow;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationKind, PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, Formatting, GotoDefinition, HoverRequest, Request as RequestKind,
+};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionParams, CompletionResponse, DiagnosticSeverity,
+    DocumentFormattingParams, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents,
+    HoverParams, HoverProviderCapability, InitializeParams, Location, MarkupContent, MarkupKind,
+    OneOf, Position, PublishDiagnosticsParams, ServerCapabilities, ServerInfo,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url,
+};
+use smallvec::SmallVec;
+
+use crate::borrowed::unescape_borrowed;
+use crate::fmt::{format, FormatOptions};
+use crate::handwritten::{TokenKind, Tokenizer};
+use crate::parser::{self, tokenize_recovering, Range};
+use crate::schema::Schema;
+use crate::selector::{parse_json_pointer, parse_path, walk_pointer};
+use crate::ser::{needs_quotes, write_string};
+use crate::KeyIndexRef;
+
+type Path<'a> = SmallVec<[KeyIndexRef<'a>; 8]>;
+
+/// Answers requests on `connection` until the client shuts the server down.
+/// Fails during initialization if the schema can't be loaded, after answering the
+/// `initialize` request with the error.
+pub fn run(connection: &Connection) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (id, params) = connection.initialize_start()?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+    let schema = match load_schema(&params) {
+        Ok(schema) => schema,
+        Err(e) => {
+            let message = format!("invalid schema: {e}");
+            connection.sender.send(Message::Response(Response::new_err(
+                id,
+                ErrorCode::InvalidParams as i32,
+                message.clone(),
+            )))?;
+            return Err(message.into());
+        }
+    };
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(Default::default()),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    };
+    let result = lsp_types::InitializeResult {
+        capabilities,
+        server_info: Some(ServerInfo {
+            name: "acf-lsp".to_string(),
+            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        }),
+    };
+    connection.initialize_finish(id, serde_json::to_value(result)?)?;
+
+    let mut server = Server {
+        documents: HashMap::new(),
+        schema,
+    };
+
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                connection
+                    .sender
+                    .send(Message::Response(server.request(request)))?;
+            }
+            Message::Notification(notification) => {
+                if let Some(diagnostics) = server.notification(notification) {
+                    connection
+                        .sender
+                        .send(Message::Notification(Notification::new(
+                            PublishDiagnostics::METHOD.to_string(),
+                            diagnostics,
+                        )))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+fn load_schema(params: &InitializeParams) -> Result<Option<Schema>, Box<dyn Error + Send + Sync>> {
+    let Some(option) = params
+        .initialization_options
+        .as_ref()
+        .and_then(|options| options.get("schema"))
+    else {
+        return Ok(None);
+    };
+
+    let schema = match option {
+        serde_json::Value::String(path) => {
+            let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+            if path.ends_with(".json") {
+                Schema::from_json(serde_json::from_str(&text)?)?
+            } else {
+                Schema::from_acf(
+                    &crate::parser::parse(&text)
+                        .map_err(|e| format!("{path}: {}: invalid syntax", e.offset()))?,
+                )?
+            }
+        }
+        value => Schema::from_json(value.clone())?,
+    };
+    Ok(Some(schema))
+}
+
+struct Server {
+    documents: HashMap<Url, String>,
+    schema: Option<Schema>,
+}
+
+impl Server {
+    fn request(&self, request: Request) -> Response {
+        match request.method.as_str() {
+            HoverRequest::METHOD => respond::<HoverRequest>(request, |params| self.hover(params)),
+            GotoDefinition::METHOD => {
+                respond::<GotoDefinition>(request, |params| self.definition(params))
+            }
+            Completion::METHOD => respond::<Completion>(request, |params| self.completion(params)),
+            Formatting::METHOD => respond::<Formatting>(request, |params| self.format(params)),
+            method => Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("unknown method {method}"),
+            ),
+        }
+    }
+
+    /// Keeps track of the open documents, returns the diagnostics to publish.
+    fn notification(&mut self, notification: Notification) -> Option<PublishDiagnosticsParams> {
+        let uri = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = notification
+                    .extract::<lsp_types::DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD)
+                    .ok()?;
+                let uri = params.text_document.uri;
+                self.documents
+                    .insert(uri.clone(), params.text_document.text);
+                uri
+            }
+            DidChangeTextDocument::METHOD => {
+                let params = notification
+                    .extract::<lsp_types::DidChangeTextDocumentParams>(
+                        DidChangeTextDocument::METHOD,
+                    )
+                    .ok()?;
+                let uri = params.text_document.uri;
+                // the server asks for full syncing, so the last change has the whole text
+                let text = params.content_changes.into_iter().last()?.text;
+                self.documents.insert(uri.clone(), text);
+                uri
+            }
+            DidCloseTextDocument::METHOD => {
+                let params = notification
+                    .extract::<lsp_types::DidCloseTextDocumentParams>(DidCloseTextDocument::METHOD)
+                    .ok()?;
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                return Some(PublishDiagnosticsParams::new(uri, Vec::new(), None));
+            }
+            _ => return None,
+        };
+
+        let text = &self.documents[&uri];
+        let (_, diagnostics) = tokenize_recovering(text);
+        let diagnostics = diagnostics
+            .into_iter()
+            .map(|diagnostic| lsp_types::Diagnostic {
+                range: range(text, &diagnostic.span),
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("acf".to_string()),
+                message: diagnostic.message,
+                ..Default::default()
+            })
+            .collect();
+        Some(PublishDiagnosticsParams::new(uri, diagnostics, None))
+    }
+
+    fn document(&self, uri: &Url) -> Option<&str> {
+        self.documents.get(uri).map(String::as_str)
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let position = params.text_document_position_params;
+        let text = self.document(&position.text_document.uri)?;
+        let at = offset(text, position.position);
+
+        let (ast, _) = tokenize_recovering(text);
+        let parser::ACF::Map(_, items) = &ast else {
+            return None;
+        };
+        let mut path = Path::new();
+        let span = path_at(text, items, at, &mut path)?;
+
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("`{}`", walk_pointer(&path)),
+            }),
+            range: Some(range(text, &span)),
+        })
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = params.text_document_position_params;
+        let uri = position.text_document.uri;
+        let text = self.document(&uri)?;
+        let at = offset(text, position.position);
+
+        let reference = Tokenizer::recovering(text)
+            .filter_map(Result::ok)
+            .find(|token| token.range.start <= at && at < token.range.end)
+            .filter(|token| matches!(token.kind, TokenKind::String | TokenKind::Word))
+            .and_then(|token| reference_at(text, token.range, at))?;
+        let path: Path = match reference.starts_with('/') {
+            true => parse_json_pointer(reference)?,
+            false => parse_path(reference)?,
+        };
+
+        let (ast, _) = tokenize_recovering(text);
+        let target = resolve(text, &ast, &path)?;
+        Some(GotoDefinitionResponse::Scalar(Location::new(
+            uri,
+            range(text, &target),
+        )))
+    }
+
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let position = params.text_document_position;
+        let text = self.document(&position.text_document.uri)?;
+        let at = offset(text, position.position);
+
+        let mut schema = self.schema.as_ref()?;
+        if let Some(key) = key_position(text, at)? {
+            schema = schema.properties.get(key.as_ref())?;
+        }
+
+        let items = schema
+            .properties
+            .iter()
+            .map(|(key, property)| {
+                let mut insert = String::new();
+                write_string(&mut insert, key).expect("writing to a string does not fail");
+                CompletionItem {
+                    label: key.to_string(),
+                    kind: Some(CompletionItemKind::PROPERTY),
+                    detail: property.kind.map(|kind| kind.to_string()),
+                    insert_text: needs_quotes(key).then_some(insert),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        Some(CompletionResponse::Array(items))
+    }
+
+    fn format(&self, params: DocumentFormattingParams) -> Option<Vec<TextEdit>> {
+        let text = self.document(&params.text_document.uri)?;
+        let options = FormatOptions {
+            indent: params.options.tab_size as usize,
+            ..FormatOptions::default()
+        };
+        let formatted = format(text, &options).ok()?;
+        if formatted == text {
+            return Some(Vec::new());
+        }
+        Some(vec![TextEdit::new(
+            range(text, &(0..text.len())),
+            formatted,
+        )])
+    }
+}
+
+fn respond<R: RequestKind>(
+    request: Request,
+    handler: impl FnOnce(R::Params) -> R::Result,
+) -> Response {
+    match serde_json::from_value(request.params) {
+        Ok(params) => Response::new_ok(request.id, handler(params)),
+        Err(e) => Response::new_err(request.id, ErrorCode::InvalidParams as i32, e.to_string()),
+    }
+}
+
+/// LSP counts characters in UTF-16 code units.
+fn position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    Position::new(
+        before.matches('\n').count() as u32,
+        before[line_start..].encode_utf16().count() as u32,
+    )
+}
+
+fn offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(index) => line_start += index + 1,
+            None => return text.len(),
+        }
+    }
+
+    let line = text[line_start..].split('\n').next().unwrap_or_default();
+    let mut units = 0;
+    for (index, ch) in line.char_indices() {
+        if units >= position.character {
+            return line_start + index;
+        }
+        units += ch.len_utf16() as u32;
+    }
+    line_start + line.len()
+}
+
+fn range(text: &str, span: &Range) -> lsp_types::Range {
+    lsp_types::Range::new(position(text, span.start), position(text, span.end))
+}
+
+fn key_text<'a>(text: &'a str, key: &Range) -> Cow<'a, str> {
+    unescape_borrowed(&text[key.clone()])
+}
+
+fn key_segment<'a>(text: &'a str, key: &Range) -> KeyIndexRef<'a> {
+    KeyIndexRef::String(match key_text(text, key) {
+        Cow::Borrowed(key) => KStringCow::from_ref(key),
+        Cow::Owned(key) => KStringCow::from_string(key),
+    })
+}
+
+/// Fills `path` with the keys and indices leading to the key or scalar at `at`, and returns
+/// its span. The end of a span counts, so a cursor right behind a word still finds it.
+fn path_at<'a>(
+    text: &'a str,
+    items: &parser::RangeMap<parser::ACF>,
+    at: usize,
+    path: &mut Path<'a>,
+) -> Option<Range> {
+    let contains = |span: &Range| span.start <= at && at <= span.end;
+
+    for (key, value) in items.iter() {
+        if contains(key) {
+            path.push(key_segment(text, key));
+            return Some(key.clone());
+        }
+        if !contains(value.as_range()) {
+            continue;
+        }
+        path.push(key_segment(text, key));
+        return match value {
+            parser::ACF::Map(_, items) => path_at(text, items, at, path),
+            parser::ACF::Seq(_, values) => {
+                let (index, value) = values
+                    .iter()
+                    .enumerate()
+                    .find(|(_, value)| contains(value.as_range()))?;
+                path.push(KeyIndexRef::Integer(index as isize));
+                Some(value.as_range().clone())
+            }
+            value => Some(value.as_range().clone()),
+        };
+    }
+    None
+}
+
+/// The text between `${` and `}` around `at` in the string or word at `token`.
+fn reference_at(text: &str, token: Range, at: usize) -> Option<&str> {
+    let mut rest = token.start;
+    while let Some(start) = text[rest..token.end].find("${") {
+        let start = rest + start;
+        let end = start + text[start..token.end].find('}')?;
+        if start <= at && at <= end {
+            return Some(&text[start + 2..end]);
+        }
+        rest = end;
+    }
+    None
+}
+
+/// Span of the key `path` ends at, or of the value for an index. With duplicate keys the
+/// last one is used, like the parsed document does.
+fn resolve(text: &str, ast: &parser::ACF, path: &[KeyIndexRef]) -> Option<Range> {
+    let (segment, rest) = path.split_first()?;
+    let (span, value) = match (segment, ast) {
+        (KeyIndexRef::String(segment), parser::ACF::Map(_, items)) => items
+            .iter()
+            .rev()
+            .find(|(key, _)| key_text(text, key) == segment.as_str())
+            .map(|(key, value)| (key.clone(), value))?,
+        (KeyIndexRef::Integer(index), parser::ACF::Seq(_, values)) => {
+            let index = match *index < 0 {
+                true => values.len().checked_sub(index.unsigned_abs())?,
+                false => *index as usize,
+            };
+            let value = values.get(index)?;
+            (value.as_range().clone(), value)
+        }
+        _ => return None,
+    };
+
+    match rest.is_empty() {
+        true => Some(span),
+        false => resolve(text, value, rest),
+    }
+}
+
+/// `None` if the cursor is not where a key goes, otherwise the key of the map value it is
+/// in, or `Some(None)` at the top level.
+fn key_position(text: &str, at: usize) -> Option<Option<Cow<'_, str>>> {
+    let mut tokens: Vec<_> = Tokenizer::recovering(&text[..at])
+        .filter_map(Result::ok)
+        .collect();
+    // the key being typed
+    if tokens.last().is_some_and(|token| {
+        token.range.end == at && matches!(token.kind, TokenKind::String | TokenKind::Word)
+    }) {
+        tokens.pop();
+    }
+    match tokens.last().map(|token| token.kind) {
+        None | Some(TokenKind::ItemSeperator | TokenKind::BracketOpen) => {}
+        _ => return None,
+    }
+
+    let mut key = None;
+    let mut parent = None;
+    for pair in tokens.windows(2) {
+        match (pair[0].kind, pair[1].kind) {
+            (TokenKind::String | TokenKind::Word, TokenKind::Assignment) => {
+                key = Some(pair[0].range.clone())
+            }
+            (TokenKind::Assignment, TokenKind::BracketOpen) => parent = key.clone(),
+            (_, TokenKind::BracketClose) => parent = None,
+            _ => {}
+        }
+    }
+    Some(parent.map(|key| key_text(text, &key)))
+}
+
+#[cfg(test)]
+struct Client {
+    connection: Connection,
+    server: std::thread::JoinHandle<()>,
+    next_id: i32,
+}
+
+#[cfg(test)]
+impl Client {
+    fn start(options: serde_json::Value) -> Client {
+        let (client, server) = Connection::memory();
+        let server = std::thread::spawn(move || run(&server).unwrap());
+        let mut client = Client {
+            connection: client,
+            server,
+            next_id: 0,
+        };
+
+        let result = client.request(
+            "initialize",
+            serde_json::json!({"capabilities": {}, "initializationOptions": options}),
+        );
+        assert_eq!("acf-lsp", result["serverInfo"]["name"]);
+        client.notify("initialized", serde_json::json!({}));
+        client
+    }
+
+    fn request(&mut self, method: &str, params: serde_json::Value) -> serde_json::Value {
+        self.next_id += 1;
+        let request = Request::new(self.next_id.into(), method.to_string(), params);
+        self.connection.sender.send(request.into()).unwrap();
+        match self.connection.receiver.recv().unwrap() {
+            Message::Response(response) => {
+                assert!(response.error.is_none(), "{:?}", response.error);
+                response.result.unwrap_or_default()
+            }
+            message => panic!("expected a response, got {message:?}"),
+        }
+    }
+
+    fn notify(&self, method: &str, params: serde_json::Value) {
+        let notification = Notification::new(method.to_string(), params);
+        self.connection.sender.send(notification.into()).unwrap();
+    }
+
+    /// Opens `text` as `file:///test.acf` and returns the published diagnostics.
+    fn open(&self, text: &str) -> serde_json::Value {
+        self.notify(
+            "textDocument/didOpen",
+            serde_json::json!({"textDocument": {
+                "uri": "file:///test.acf", "languageId": "acf", "version": 1, "text": text
+            }}),
+        );
+        match self.connection.receiver.recv().unwrap() {
+            Message::Notification(notification) => {
+                assert_eq!("textDocument/publishDiagnostics", notification.method);
+                notification.params["diagnostics"].clone()
+            }
+            message => panic!("expected diagnostics, got {message:?}"),
+        }
+    }
+
+    fn at(&mut self, method: &str, line: u32, character: u32) -> serde_json::Value {
+        self.request(
+            method,
+            serde_json::json!({
+                "textDocument": {"uri": "file:///test.acf"},
+                "position": {"line": line, "character": character}
+            }),
+        )
+    }
+
+    fn shutdown(mut self) {
+        assert_eq!(
+            serde_json::Value::Null,
+            self.request("shutdown", serde_json::Value::Null)
+        );
+        self.notify("exit", serde_json::Value::Null);
+        self.server.join().unwrap();
+    }
+}
+
+#[test]
+fn lsp_diagnostics_test() {
+    let client = Client::start(serde_json::Value::Null);
+
+    let diagnostics = client.open("a=1,\nb=\"\\q\", c={}");
+    assert_eq!(
+        serde_json::json!([
+            {
+                "range": {"start": {"line": 1, "character": 3}, "end": {"line": 1, "character": 6}},
+                "severity": 1,
+                "source": "acf",
+                "message": "invalid escape sequence"
+            },
+            {
+                "range": {"start": {"line": 1, "character": 10}, "end": {"line": 1, "character": 12}},
+                "severity": 1,
+                "source": "acf",
+                "message": "`{}` needs at least one item"
+            }
+        ]),
+        diagnostics
+    );
+
+    client.notify(
+        "textDocument/didChange",
+        serde_json::json!({
+            "textDocument": {"uri": "file:///test.acf", "version": 2},
+            "contentChanges": [{"text": "a=1"}]
+        }),
+    );
+    match client.connection.receiver.recv().unwrap() {
+        Message::Notification(notification) => {
+            assert_eq!(serde_json::json!([]), notification.params["diagnostics"])
+        }
+        message => panic!("expected diagnostics, got {message:?}"),
+    }
+
+    let unknown = Request::new(
+        100.into(),
+        "acf/unknown".to_string(),
+        serde_json::Value::Null,
+    );
+    client.connection.sender.send(unknown.into()).unwrap();
+    match client.connection.receiver.recv().unwrap() {
+        Message::Response(response) => assert_eq!(
+            ErrorCode::MethodNotFound as i32,
+            response.error.unwrap().code
+        ),
+        message => panic!("expected a response, got {message:?}"),
+    }
+
+    client.shutdown();
+}
+
+#[test]
+fn lsp_hover_and_definition_test() {
+    let mut client = Client::start(serde_json::Value::Null);
+    client.open("db={host: \"a/b\", \"p~rt\": 5432},\nhosts={x, y},\nurl=\"${db.host}:${/db/p~0rt}\", first=\"${hosts[-2]}\"");
+
+    let hover = |client: &mut Client, line, character| {
+        client.at("textDocument/hover", line, character)["contents"]["value"].clone()
+    };
+    assert_eq!("`/db`", hover(&mut client, 0, 1));
+    assert_eq!("`/db/host`", hover(&mut client, 0, 6));
+    assert_eq!("`/db/host`", hover(&mut client, 0, 12));
+    assert_eq!("`/db/p~0rt`", hover(&mut client, 0, 26));
+    assert_eq!("`/hosts/1`", hover(&mut client, 1, 11));
+    assert_eq!("`/url`", hover(&mut client, 2, 10));
+    assert_eq!(
+        serde_json::Value::Null,
+        client.at("textDocument/hover", 0, 30)
+    );
+
+    let definition = |client: &mut Client, line, character| {
+        client.at("textDocument/definition", line, character)["range"].clone()
+    };
+    let span = |start, end| {
+        serde_json::json!({
+            "start": {"line": 0, "character": start}, "end": {"line": 0, "character": end}
+        })
+    };
+    assert_eq!(span(4, 8), definition(&mut client, 2, 8));
+    assert_eq!(span(17, 23), definition(&mut client, 2, 20));
+    assert_eq!(
+        serde_json::json!({
+            "start": {"line": 1, "character": 7}, "end": {"line": 1, "character": 8}
+        }),
+        definition(&mut client, 2, 39)
+    );
+    assert_eq!(
+        serde_json::Value::Null,
+        client.at("textDocument/definition", 2, 2)
+    );
+
+    client.shutdown();
+}
+
+#[test]
+fn lsp_completion_test() {
+    let schema = serde_json::json!({"schema": {"properties": {
+        "db": {"type": "object", "properties": {"host": {"type": "string"}, "max conns": {}}},
+        "name": {"type": "string"}
+    }}});
+    let mut client = Client::start(schema);
+    client.open("name=x, na\ndb={host: a, ");
+
+    let labels = |client: &mut Client, line, character| {
+        let items = client.at("textDocument/completion", line, character);
+        items
+            .as_array()
+            .map(|items| {
+                items
+                    .iter()
+                    .map(|item| {
+                        let insert = item.get("insertText").and_then(|text| text.as_str());
+                        (
+                            item["label"].as_str().unwrap().to_string(),
+                            insert.map(str::to_string),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+    };
+
+    let top = vec![("db".to_string(), None), ("name".to_string(), None)];
+    assert_eq!(top, labels(&mut client, 0, 0));
+    assert_eq!(top, labels(&mut client, 0, 10));
+    assert_eq!(
+        vec![
+            ("host".to_string(), None),
+            ("max conns".to_string(), Some("\"max conns\"".to_string()))
+        ],
+        labels(&mut client, 1, 12)
+    );
+    // values are not keys
+    assert!(labels(&mut client, 0, 5).is_empty());
+    assert!(labels(&mut client, 1, 10).is_empty());
+
+    client.shutdown();
+}
+
+#[test]
+fn lsp_formatting_test() {
+    let mut client = Client::start(serde_json::Value::Null);
+    let format = |client: &mut Client| {
+        client.request(
+            "textDocument/formatting",
+            serde_json::json!({
+                "textDocument": {"uri": "file:///test.acf"},
+                "options": {"tabSize": 4, "insertSpaces": true}
+            }),
+        )
+    };
+
+    client.open("a = 1 ,\nb={ x :\"y\" }, c = !secret hunter2");
+    let edits = format(&mut client);
+    assert_eq!(
+        serde_json::json!([{
+            "range": {"start": {"line": 0, "character": 0}, "end": {"line": 1, "character": 33}},
+            "newText": "a=1, b={x: y}, c=!secret hunter2"
+        }]),
+        edits
+    );
+
+    let formatted = edits[0]["newText"].as_str().unwrap().to_string();
+    client.open(&formatted);
+    assert_eq!(serde_json::json!([]), format(&mut client));
+
+    client.open("a=1 b");
+    assert_eq!(serde_json::Value::Null, format(&mut client));
+    client.open("a = @file(b.acf)");
+    assert_eq!(
+        "a=@file(b.acf)",
+        format(&mut client)[0]["newText"].as_str().unwrap()
+    );
+
+    client.shutdown();
+}
+
+#[test]
+fn position_test() {
+    let text = "a=\"é😀\",\r\nb=1\n";
+    assert_eq!(Position::new(0, 0), position(text, 0));
+    assert_eq!(Position::new(0, 4), position(text, 5));
+    assert_eq!(Position::new(0, 6), position(text, 9));
+    assert_eq!(Position::new(1, 2), position(text, text.len() - 2));
+    assert_eq!(Position::new(2, 0), position(text, text.len()));
+
+    for (at, _) in text.char_indices() {
+        assert_eq!(at, offset(text, position(text, at)));
+    }
+    assert_eq!(text.len() - 1, offset(text, Position::new(1, 99)));
+    assert_eq!(text.len(), offset(text, Position::new(5, 0)));
+}
+
+#[test]
+fn lsp_invalid_schema_test() {
+    let (client, server) = Connection::memory();
+    let server = std::thread::spawn(move || run(&server).map_err(|e| e.to_string()));
+
+    let params = serde_json::json!({
+        "capabilities": {},
+        "initializationOptions": {"schema": "/nonexistent/schema.acf"},
+    });
+    let request = Request::new(1.into(), "initialize".to_string(), params);
+    client.sender.send(request.into()).unwrap();
+
+    let Message::Response(response) = client.receiver.recv().unwrap() else {
+        panic!("expected a response");
+    };
+    let error = response.error.unwrap();
+    assert_eq!(ErrorCode::InvalidParams as i32, error.code);
+    assert!(
+        error
+            .message
+            .starts_with("invalid schema: /nonexistent/schema.acf: "),
+        "{}",
+        error.message
+    );
+    assert_eq!(Err(error.message), server.join().unwrap());
+}
@@ -0,0 +1,318 @@
+use std::io::Read;
+use std::process::ExitCode;
+
+use acf::export::{Layout, Shell};
+use acf::include::{self, FileLoader};
+use acf::schema::Schema;
+use acf::ser::Serializer;
+use acf::{dotenv, parser, tokenized_to_config, ACF};
+
+const USAGE: &str = "\
+usage: acf <command> [options] [FILE]
+
+Reads FILE, or stdin when FILE is missing or '-'.
+
+commands:
+  export [--format sh|fish|dotenv] [--name NAME | --prefix PREFIX] [FILE]
+      Print the config as shell variables. With --name the whole config is put in one
+      variable, otherwise every value gets its own variable, e.g. PREFIX_DB__PORT.
+  convert [--from FORMAT] [--to FORMAT] [FILE]
+      Convert between acf, json, dotenv (input only), toml and yaml. --from defaults to
+      the extension of FILE, --to defaults to acf.
+  check [--schema SCHEMA] [FILE]
+      Report all syntax errors, and with --schema the values that don't match SCHEMA.
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Acf,
+    Json,
+    Dotenv,
+    Toml,
+    Yaml,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "acf" => Ok(Format::Acf),
+            "json" => Ok(Format::Json),
+            "env" | "dotenv" => Ok(Format::Dotenv),
+            "toml" => Ok(Format::Toml),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            other => Err(format!("unknown format '{other}'")),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = match args.first().map(String::as_str) {
+        Some("export") => export(&args[1..]),
+        Some("convert") => convert(&args[1..]),
+        Some("check") => check(&args[1..]),
+        Some("-h" | "--help") => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Some(command) => Err(format!("unknown command '{command}'\n\n{USAGE}")),
+        None => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(out) => {
+            print!("{out}");
+            ExitCode::SUCCESS
+        }
+        Err(message) => {
+            eprintln!("{}", message.trim_end());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn export(args: &[String]) -> Result<String, String> {
+    let mut shell = Shell::Sh;
+    let mut name = None;
+    let mut prefix = String::new();
+    let mut path = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--format" => shell = value()?.parse()?,
+            "--name" => name = Some(value()?.to_string()),
+            "--prefix" => prefix = value()?.to_string(),
+            flag if flag.starts_with("--") => return Err(format!("unknown option '{flag}'")),
+            file => path = Some(file),
+        }
+    }
+
+    let layout = match name {
+        Some(name) => Layout::Single(name),
+        None => Layout::Flatten { prefix },
+    };
+
+    acf::export::export(&read(path)?, shell, &layout).map_err(|e| e.to_string())
+}
+
+fn convert(args: &[String]) -> Result<String, String> {
+    let mut from = None;
+    let mut to = Format::Acf;
+    let mut path = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--from" => from = Some(value()?.parse()?),
+            "--to" => to = value()?.parse()?,
+            flag if flag.starts_with("--") => return Err(format!("unknown option '{flag}'")),
+            file => path = Some(file),
+        }
+    }
+
+    let config = load(from.unwrap_or_else(|| format_of(path)), path)?;
+
+    let mut out = write(to, &config)?;
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn check(args: &[String]) -> Result<String, String> {
+    let mut schema = None;
+    let mut path = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--schema" => schema = Some(value()?.as_str()),
+            flag if flag.starts_with("--") => return Err(format!("unknown option '{flag}'")),
+            file => path = Some(file),
+        }
+    }
+
+    let input = read_text(path)?;
+    let name = display_name(path);
+    let (tokens, diagnostics) = parser::tokenize_recovering(&input);
+
+    let mut problems: Vec<String> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let (line, column) = line_column(&input, diagnostic.span.start);
+            format!("{name}:{line}:{column}: {}", diagnostic.message)
+        })
+        .collect();
+
+    let schema = match schema {
+        Some(schema) => Some(
+            Schema::from_acf(&load(format_of(Some(schema)), Some(schema))?)
+                .map_err(|e| format!("{schema}: {e}"))?,
+        ),
+        None => None,
+    };
+    // the parts lost to syntax errors would show up as violations
+    if let (Some(schema), true) = (schema, diagnostics.is_empty()) {
+        let config = tokenized_to_config(&input, tokens);
+        problems.extend(
+            schema
+                .validate(&config)
+                .iter()
+                .map(|violation| format!("{name}: {violation}")),
+        );
+    }
+
+    match problems.is_empty() {
+        true => Ok(String::new()),
+        false => Err(problems.join("\n")),
+    }
+}
+
+/// 1-based line and column of a byte offset.
+fn line_column(input: &str, offset: usize) -> (usize, usize) {
+    let before = &input[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
+
+/// The format for the extension of `path`, ACF if it is unknown.
+fn format_of(path: Option<&str>) -> Format {
+    path.and_then(|path| path.rsplit_once('.'))
+        .and_then(|(_, extension)| extension.parse().ok())
+        .unwrap_or(Format::Acf)
+}
+
+fn display_name(path: Option<&str>) -> &str {
+    path.filter(|path| *path != "-").unwrap_or("<stdin>")
+}
+
+/// Reads `path` in `format`, ACF files with their includes.
+fn load(format: Format, path: Option<&str>) -> Result<ACF, String> {
+    match format {
+        Format::Acf => read(path),
+        format => {
+            parse(format, &read_text(path)?).map_err(|e| format!("{}: {e}", display_name(path)))
+        }
+    }
+}
+
+fn parse(format: Format, input: &str) -> Result<ACF, String> {
+    match format {
+        Format::Acf => {
+            let tokens = parser::tokenize_ast(input)
+                .map_err(|e| format!("{}: invalid syntax", e.offset()))?;
+            Ok(tokenized_to_config(input, tokens))
+        }
+        Format::Json => {
+            let value: serde_json::Value =
+                serde_json::from_str(input).map_err(|e| e.to_string())?;
+            ACF::try_from(value).map_err(|e| e.to_string())
+        }
+        Format::Dotenv => dotenv::parse(input).map_err(|e| e.to_string()),
+        #[cfg(feature = "toml")]
+        Format::Toml => acf::toml::from_str(input).map_err(|e| e.to_string()),
+        #[cfg(feature = "yaml")]
+        Format::Yaml => acf::yaml::from_str(input).map_err(|e| e.to_string()),
+        #[allow(unreachable_patterns)]
+        format => Err(not_enabled(format)),
+    }
+}
+
+fn write(format: Format, config: &ACF) -> Result<String, String> {
+    match format {
+        // ACF values only nest one level deep, unlike JSON, TOML or YAML
+        Format::Acf => Serializer::new()
+            .reveal_secrets(true)
+            .to_string(config)
+            .map_err(|e| format!("can't write as ACF: {e}")),
+        Format::Json => serde_json::to_string_pretty(&serde_json::Value::from(config.clone()))
+            .map_err(|e| e.to_string()),
+        Format::Dotenv => Err("use `acf export --format dotenv` to write dotenv files".to_string()),
+        #[cfg(feature = "toml")]
+        Format::Toml => acf::toml::to_string(config).map_err(|e| e.to_string()),
+        #[cfg(feature = "yaml")]
+        Format::Yaml => acf::yaml::to_string(config).map_err(|e| e.to_string()),
+        #[allow(unreachable_patterns)]
+        format => Err(not_enabled(format)),
+    }
+}
+
+fn not_enabled(format: Format) -> String {
+    let feature = format!("{format:?}").to_lowercase();
+    format!("{feature} support is not enabled, rebuild with `--features {feature}`")
+}
+
+fn read_text(path: Option<&str>) -> Result<String, String> {
+    match path {
+        Some("-") | None => {
+            let mut input = String::new();
+            std::io::stdin()
+                .read_to_string(&mut input)
+                .map_err(|e| format!("<stdin>: {e}"))?;
+            Ok(input)
+        }
+        Some(path) => std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}")),
+    }
+}
+
+/// Reads a file with its includes, or stdin.
+fn read(path: Option<&str>) -> Result<ACF, String> {
+    match path {
+        Some("-") | None => {
+            parse(Format::Acf, &read_text(path)?).map_err(|e| format!("<stdin>:{e}"))
+        }
+        Some(path) => include::load(&FileLoader, path).map_err(|e| e.to_string()),
+    }
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn convert_toml_test() {
+    let path = std::env::temp_dir().join(format!("acf-convert-{}.toml", std::process::id()));
+    std::fs::write(
+        &path,
+        "name = \"demo\"\ntags = [\"web\", \"api\"]\n\n[server]\nhost = \"localhost\"\nport = 8080\n",
+    )
+    .unwrap();
+    let args = |path: &str| {
+        ["--from", "toml", "--to", "acf", path]
+            .map(String::from)
+            .to_vec()
+    };
+
+    let out = convert(&args(path.to_str().unwrap()));
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+        Ok("name=demo,server={host: localhost, port: 8080},tags={web, api}\n".to_string()),
+        out
+    );
+
+    let manifest = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+    assert_eq!(
+        Err("can't write as ACF: /bench/0: maps and sequences can't be nested".to_string()),
+        convert(&args(manifest))
+    );
+}
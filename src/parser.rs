@@ -1,4 +1,4 @@
-use winnow::ascii::multispace0;
+use winnow::ascii::{multispace0, multispace1};
 use winnow::combinator::{
     alt, cut_err, delimited, preceded, repeat_till, separated, separated_pair,
 };
@@ -7,7 +7,16 @@ use winnow::prelude::*;
 use winnow::stream::{Located, Location};
 use winnow::token::{any, none_of, take_while};
 
+mod build;
+mod options;
+mod recover;
 pub mod types;
+pub mod visit;
+
+pub use build::parse;
+pub(crate) use build::primative_value;
+pub use options::{parse_with_options, LimitError, ParseOptions};
+pub use recover::{tokenize_recovering, Diagnostic};
 pub use types::{Map, Range, RangeMap, Seq};
 
 const PARSE_FORMAT: u128 = lexical::format::TOML;
@@ -20,6 +29,7 @@ pub enum ACF {
     Integer(Range),
     Float(Range),
     Boolean(Range),
+    Secret(Range, Box<ACF>),
     Seq(Range, Seq<ACF>),
     Map(Range, RangeMap<ACF>),
 }
@@ -31,6 +41,7 @@ impl ACF {
             ACF::Integer(range) => range,
             ACF::Float(range) => range,
             ACF::Boolean(range) => range,
+            ACF::Secret(range, _) => range,
             ACF::Seq(range, _) => range,
             ACF::Map(range, _) => range,
         }
@@ -42,19 +53,38 @@ impl ACF {
             ACF::Integer(range) => range,
             ACF::Float(range) => range,
             ACF::Boolean(range) => range,
+            ACF::Secret(range, _) => range,
             ACF::Seq(range, _) => range,
             ACF::Map(range, _) => range,
         }
     }
 }
 
-const SPECIAL_CHARS: [char; 5] = ['=', ',', '{', '}', ':'];
+pub const SPECIAL_CHARS: [char; 5] = ['=', ',', '{', '}', ':'];
 
 pub fn parse_integer(x: &str) -> Result<i64, lexical::Error> {
+    // lexical stops after a leading zero, so "0.5" or "0x10" would otherwise parse as 0
+    let digits = x.strip_prefix(['+', '-']).unwrap_or(x);
+    if digits.starts_with('0') && digits.len() > 1 {
+        return Err(lexical::Error::InvalidDigit(x.len() - digits.len() + 1));
+    }
     lexical::parse_with_options::<i64, _, PARSE_FORMAT>(x, &PARSE_INTEGER_OPTION)
 }
 
 pub fn parse_float(x: &str) -> Result<f64, lexical::Error> {
+    // lexical reads a missing mantissa or exponent as 0, so "e", "." or "1e" would otherwise
+    // parse as a float
+    let mut parts = x.splitn(2, ['e', 'E']);
+    let mantissa = parts.next().unwrap_or_default();
+    if !mantissa.contains(|ch: char| ch.is_ascii_digit()) {
+        return Err(lexical::Error::EmptyMantissa(0));
+    }
+    if parts
+        .next()
+        .is_some_and(|exponent| !exponent.contains(|ch: char| ch.is_ascii_digit()))
+    {
+        return Err(lexical::Error::EmptyExponent(mantissa.len() + 1));
+    }
     lexical::parse_with_options::<f64, _, PARSE_FORMAT>(x, &PARSE_FLOAT_OPTION)
 }
 
@@ -75,11 +105,8 @@ fn string<'s>(input: &mut Located<&'s str>) -> PResult<&'s str, InputError<Locat
 fn character<'s>(input: &mut Located<&'s str>) -> PResult<char, InputError<Located<&'s str>>> {
     let c = none_of('\"').parse_next(input)?;
     if c == '\\' {
-        any.verify(|c| match c {
-            '"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't' => true,
-            _ => false,
-        })
-        .parse_next(input)
+        any.verify(|c| matches!(c, '"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't'))
+            .parse_next(input)
     } else {
         Ok(c)
     }
@@ -119,6 +146,20 @@ fn primative_parser<'s>(
         })
 }
 
+fn secret_parser<'s>(input: &mut Located<&'s str>) -> PResult<ACF, InputError<Located<&'s str>>> {
+    let start = input.location();
+    preceded(("!secret", multispace1), cut_err(primative_parser))
+        .parse_next(input)
+        .map(|value| {
+            let end = input.location();
+            ACF::Secret(start..end, Box::new(value))
+        })
+}
+
+fn scalar_parser<'s>(input: &mut Located<&'s str>) -> PResult<ACF, InputError<Located<&'s str>>> {
+    alt((secret_parser, primative_parser)).parse_next(input)
+}
+
 fn range_parser<'s>(input: &mut Located<&'s str>) -> PResult<Range, InputError<Located<&'s str>>> {
     delimited(ws, primative_parser.map(|x| x.into_range()), ws).parse_next(input)
 }
@@ -137,14 +178,14 @@ fn map_item_parser<'s>(
 ) -> PResult<(Range, ACF), InputError<Located<&'s str>>> {
     delimited(
         ws,
-        separated_pair(range_parser, (ws, ":", ws), primative_parser),
+        separated_pair(range_parser, (ws, ":", ws), scalar_parser),
         ws,
     )
     .parse_next(input)
 }
 
 fn seq_item_parser<'s>(input: &mut Located<&'s str>) -> PResult<ACF, InputError<Located<&'s str>>> {
-    delimited(ws, primative_parser, ws).parse_next(input)
+    delimited(ws, scalar_parser, ws).parse_next(input)
 }
 
 fn list_item_parser<'s>(
@@ -192,7 +233,7 @@ fn composite_parser<'s>(
 }
 
 fn value_parser<'s>(input: &mut Located<&'s str>) -> PResult<ACF, InputError<Located<&'s str>>> {
-    delimited(ws, alt((composite_parser, primative_parser)), ws).parse_next(input)
+    delimited(ws, alt((composite_parser, scalar_parser)), ws).parse_next(input)
 }
 
 fn item_parser<'s>(
@@ -222,12 +263,19 @@ fn ws<'s>(input: &mut Located<&'s str>) -> PResult<&'s str, InputError<Located<&
     multispace0.parse_next(input)
 }
 
-pub fn tokenize_ast<'s>(
-    data: &'s str,
-) -> Result<ACF, ParseError<Located<&'s str>, InputError<Located<&'s str>>>> {
+pub fn tokenize_ast(
+    data: &str,
+) -> Result<ACF, ParseError<Located<&str>, InputError<Located<&str>>>> {
     base_parser.parse(Located::new(data))
 }
 
+/// Tokenizes a single scalar such as `12`, `"quoted text"` or `!secret value`.
+pub fn tokenize_scalar(
+    data: &str,
+) -> Result<ACF, ParseError<Located<&str>, InputError<Located<&str>>>> {
+    delimited(ws, scalar_parser, ws).parse(Located::new(data))
+}
+
 #[cfg(test)]
 fn debug_visit_ast<'a>(input: &'a str, acf: &ACF, out: &mut Vec<(char, &'a str)>) {
     match acf {
@@ -243,6 +291,10 @@ fn debug_visit_ast<'a>(input: &'a str, acf: &ACF, out: &mut Vec<(char, &'a str)>
         ACF::Boolean(range) => {
             out.push(('b', &input[range.clone()]));
         }
+        ACF::Secret(range, value) => {
+            out.push(('x', &input[range.clone()]));
+            debug_visit_ast(input, value, out);
+        }
         ACF::Seq(range, rest) => {
             out.push(('l', &input[range.clone()]));
             for item in rest.iter() {
@@ -442,3 +494,81 @@ fn escaped_text_bracket() {
 
     assert_eq!(expected, strings);
 }
+
+#[test]
+fn tokenize_secret() {
+    let data = r#"a=!secret "top secret",b={x: !secret   1},c={!secret true},d=!secretive"#;
+    let out = tokenize_ast(data).unwrap();
+
+    let mut strings = Vec::new();
+    debug_visit_ast(data, &out, &mut strings);
+    let expected = vec![
+        ('m', data),
+        ('k', "a"),
+        ('x', r#"!secret "top secret""#),
+        ('s', r#""top secret""#),
+        ('k', "b"),
+        ('m', "x: !secret   1"),
+        ('k', "x"),
+        ('x', "!secret   1"),
+        ('i', "1"),
+        ('k', "c"),
+        ('l', "!secret true"),
+        ('x', "!secret true"),
+        ('b', "true"),
+        ('k', "d"),
+        ('s', "!secretive"),
+    ];
+
+    assert_eq!(expected, strings);
+}
+
+#[test]
+fn tokenize_scalar_test() {
+    assert_eq!(Ok(ACF::Integer(1..3)), tokenize_scalar(" 12 "));
+    assert_eq!(Ok(ACF::String(0..6)), tokenize_scalar(r#""a, b""#));
+    assert!(tokenize_scalar("a b").is_err());
+    assert!(tokenize_scalar("{1}").is_err());
+}
+
+#[test]
+fn parse_integer_whole_token() {
+    for input in [
+        "0abc", "0x10", "0b1", "0_", "0-1", "0.5", "0e1", "01", "-01", "1_000x",
+    ] {
+        assert!(parse_integer(input).is_err(), "{input}");
+    }
+    assert_eq!(Ok(0), parse_integer("0"));
+    assert_eq!(Ok(0), parse_integer("-0"));
+    assert_eq!(Ok(-12), parse_integer("-12"));
+    assert_eq!(Ok(1000), parse_integer("1_000"));
+}
+
+#[test]
+fn parse_float_needs_digits() {
+    for input in [
+        "e", "E5", ".", "-", "+e1", ".e1", "1e", "1e+", "0e", "nan", "inf",
+    ] {
+        assert!(parse_float(input).is_err(), "{input}");
+    }
+    assert_eq!(Ok(0.5), parse_float("0.5"));
+    assert_eq!(Ok(0.5), parse_float(".5"));
+    assert_eq!(Ok(5.0), parse_float("5."));
+    assert_eq!(Ok(0.01), parse_float("1E-2"));
+    assert_eq!(Ok(-1500.0), parse_float("-1.5e3"));
+}
+
+#[test]
+fn tokenize_scalar_number_test() {
+    assert_eq!(Ok(ACF::String(0..1)), tokenize_scalar("e"));
+    assert_eq!(Ok(ACF::String(0..1)), tokenize_scalar("."));
+    assert_eq!(Ok(ACF::String(0..2)), tokenize_scalar("1e"));
+    assert_eq!(Ok(ACF::String(0..4)), tokenize_scalar("0x10"));
+    assert_eq!(Ok(ACF::String(0..2)), tokenize_scalar("01"));
+    assert_eq!(Ok(ACF::String(0..3)), tokenize_scalar("0-1"));
+    assert_eq!(Ok(ACF::Float(0..3)), tokenize_scalar("0.5"));
+    assert_eq!(Ok(ACF::Float(0..4)), tokenize_scalar("-0.5"));
+    assert_eq!(Ok(ACF::Float(0..3)), tokenize_scalar("0e1"));
+    assert_eq!(Ok(ACF::Float(0..3)), tokenize_scalar("1e5"));
+    assert_eq!(Ok(ACF::Integer(0..1)), tokenize_scalar("0"));
+}
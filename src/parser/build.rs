@@ -0,0 +1,128 @@
+//! Single pass parser that builds [`crate::ACF`] directly, without the intermediate tree of
+//! ranges and without parsing numbers a second time. Uses the same grammar as [`super::tokenize_ast`].
+
+use snailquote::unescape;
+use winnow::ascii::multispace1;
+use winnow::combinator::{alt, cut_err, delimited, preceded, separated, separated_pair};
+use winnow::error::{InputError, ParseError};
+use winnow::prelude::*;
+use winnow::stream::Located;
+
+use super::{parse_float, parse_integer, take_single_primative_parser, ws};
+use crate::{Secret, StringMap, ACF};
+
+type Input<'s> = Located<&'s str>;
+type Error<'s> = InputError<Input<'s>>;
+
+fn primative<'s>(input: &mut Input<'s>) -> PResult<ACF, Error<'s>> {
+    take_single_primative_parser
+        .recognize()
+        .map(primative_value)
+        .parse_next(input)
+}
+
+/// Types the text of a primitive, including the quotes of quoted strings.
+pub(crate) fn primative_value(text: &str) -> ACF {
+    match text {
+        "true" => ACF::Boolean(true),
+        "false" => ACF::Boolean(false),
+        text => match parse_integer(text) {
+            Ok(integer) => ACF::Integer(integer),
+            Err(_) => match parse_float(text) {
+                Ok(float) => ACF::from(float),
+                Err(_) => ACF::String(unescape(text).unwrap_or_default().into()),
+            },
+        },
+    }
+}
+
+fn scalar<'s>(input: &mut Input<'s>) -> PResult<ACF, Error<'s>> {
+    alt((
+        preceded(("!secret", multispace1), cut_err(primative))
+            .map(|value| ACF::Secret(Secret::new(Box::new(value)))),
+        primative,
+    ))
+    .parse_next(input)
+}
+
+fn key<'s>(input: &mut Input<'s>) -> PResult<String, Error<'s>> {
+    delimited(ws, take_single_primative_parser.recognize(), ws)
+        .map(|key| unescape(key).unwrap_or_default())
+        .parse_next(input)
+}
+
+fn map_item<'s>(input: &mut Input<'s>) -> PResult<(String, ACF), Error<'s>> {
+    delimited(ws, separated_pair(key, (ws, ":", ws), scalar), ws).parse_next(input)
+}
+
+fn seq_item<'s>(input: &mut Input<'s>) -> PResult<ACF, Error<'s>> {
+    delimited(ws, scalar, ws).parse_next(input)
+}
+
+fn composite<'s>(input: &mut Input<'s>) -> PResult<ACF, Error<'s>> {
+    delimited(
+        (ws, "{", ws),
+        alt((
+            separated(1.., map_item, (ws, ",", ws))
+                .map(|items: Vec<_>| ACF::Map(items.into_iter().collect())),
+            separated(1.., seq_item, (ws, ",", ws)).map(ACF::Seq),
+        )),
+        (ws, "}", ws),
+    )
+    .parse_next(input)
+}
+
+fn value<'s>(input: &mut Input<'s>) -> PResult<ACF, Error<'s>> {
+    delimited(ws, alt((composite, scalar)), ws).parse_next(input)
+}
+
+fn item<'s>(input: &mut Input<'s>) -> PResult<(String, ACF), Error<'s>> {
+    delimited(ws, separated_pair(key, (ws, "=", ws), value), ws).parse_next(input)
+}
+
+fn document<'s>(input: &mut Input<'s>) -> PResult<ACF, Error<'s>> {
+    delimited(ws, separated(0.., item, (ws, ",", ws)), ws)
+        .map(|items: Vec<_>| ACF::Map(items.into_iter().collect::<StringMap<ACF>>()))
+        .parse_next(input)
+}
+
+/// Parses a document, same as `tokenized_to_config(data, tokenize_ast(data)?)`.
+pub fn parse(data: &str) -> Result<ACF, ParseError<Input<'_>, Error<'_>>> {
+    document.parse(Located::new(data))
+}
+
+#[test]
+fn parse_matches_two_stage_test() {
+    let inputs = [
+        "",
+        "a=1",
+        " a = 1 , b\t=\n\"two, 2\" ",
+        "config1={value: 1, default: 12, yes: true},config2={DEFAULT: \"testing\", extra: \"extra \\\"quotes\\\"\"},config3={false, 123, 1.23}",
+        "a={x: !secret 1, \"y z\": 'q'}, b={!secret \"p w\", e, 0.5, -3, 1_000}, c=!secret true",
+        "dup=1,dup=2",
+    ];
+
+    for input in inputs {
+        let expected = crate::tokenized_to_config(input, super::tokenize_ast(input).unwrap());
+        assert_eq!(
+            Ok(expected),
+            parse(input).map_err(|e| e.offset()),
+            "{input}"
+        );
+    }
+
+    for input in [
+        "a=1 b=2",
+        "a={}",
+        "a={1,}",
+        "a={x: {y: 1}}",
+        "a=!secret",
+        "a=\"x",
+    ] {
+        assert_eq!(
+            super::tokenize_ast(input).map_err(|e| e.offset()).err(),
+            parse(input).map_err(|e| e.offset()).err(),
+            "{input}"
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Limits for parsing untrusted input. They are checked on the tokens of the hand-written
+//! lexer before the document is parsed, which needs no recursion and no allocation.
+
+use crate::handwritten::{TokenKind, Tokenizer};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// How deep `{}` may be nested, `0` only allows scalars. The grammar allows one level.
+    pub max_depth: usize,
+    /// Length of the input in bytes.
+    pub max_len: usize,
+    /// Number of keys in the whole document, including the keys of map values.
+    pub max_keys: usize,
+    /// Length in bytes of a key or string, without the quotes.
+    pub max_string_len: usize,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            max_depth: 1,
+            max_len: 1024 * 1024,
+            max_keys: 10_000,
+            max_string_len: 64 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LimitError {
+    /// The input is not valid, `offset` is the byte position of the error.
+    Syntax {
+        offset: usize,
+    },
+    TooLong {
+        len: usize,
+        max: usize,
+    },
+    /// The `{` at `offset` is nested deeper than `max`.
+    TooDeep {
+        offset: usize,
+        max: usize,
+    },
+    /// The key at `offset` is one more than `max`.
+    TooManyKeys {
+        offset: usize,
+        max: usize,
+    },
+    StringTooLong {
+        offset: usize,
+        max: usize,
+    },
+}
+
+impl std::fmt::Display for LimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitError::Syntax { offset } => write!(f, "{offset}: invalid syntax"),
+            LimitError::TooLong { len, max } => {
+                write!(f, "input is {len} bytes long, at most {max} are allowed")
+            }
+            LimitError::TooDeep { offset, max } => {
+                write!(f, "{offset}: nested deeper than {max}")
+            }
+            LimitError::TooManyKeys { offset, max } => {
+                write!(f, "{offset}: more than {max} keys")
+            }
+            LimitError::StringTooLong { offset, max } => {
+                write!(f, "{offset}: string is longer than {max} bytes")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LimitError {}
+
+impl ParseOptions {
+    /// Checks the limits, syntax errors are left to the parser.
+    pub fn check(&self, data: &str) -> Result<(), LimitError> {
+        if data.len() > self.max_len {
+            return Err(LimitError::TooLong {
+                len: data.len(),
+                max: self.max_len,
+            });
+        }
+
+        let mut depth = 0;
+        let mut keys = 0;
+        for token in Tokenizer::new(data) {
+            let Ok(token) = token else {
+                break;
+            };
+            let offset = token.range.start;
+
+            match token.kind {
+                TokenKind::BracketOpen => {
+                    depth += 1;
+                    if depth > self.max_depth {
+                        return Err(LimitError::TooDeep {
+                            offset,
+                            max: self.max_depth,
+                        });
+                    }
+                }
+                TokenKind::BracketClose => depth = depth.saturating_sub(1),
+                TokenKind::Assignment | TokenKind::KeySeperator => {
+                    keys += 1;
+                    if keys > self.max_keys {
+                        return Err(LimitError::TooManyKeys {
+                            offset,
+                            max: self.max_keys,
+                        });
+                    }
+                }
+                TokenKind::String | TokenKind::Word => {
+                    let quotes = match token.kind {
+                        TokenKind::String => 2,
+                        _ => 0,
+                    };
+                    if token.range.len() - quotes > self.max_string_len {
+                        return Err(LimitError::StringTooLong {
+                            offset,
+                            max: self.max_string_len,
+                        });
+                    }
+                }
+                TokenKind::Secret | TokenKind::ItemSeperator => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses `data` if it is within the limits of `options`.
+pub fn parse_with_options(data: &str, options: &ParseOptions) -> Result<crate::ACF, LimitError> {
+    options.check(data)?;
+    super::parse(data).map_err(|e| LimitError::Syntax { offset: e.offset() })
+}
+
+#[test]
+fn parse_with_options_test() {
+    let options = ParseOptions::default();
+    let data = "a=1,b={x: \"y\"},c={1, 2}";
+    assert_eq!(
+        Ok(super::parse(data).unwrap()),
+        parse_with_options(data, &options)
+    );
+    assert_eq!(
+        Err(LimitError::Syntax { offset: 4 }),
+        parse_with_options("a=1 b=2", &options)
+    );
+
+    let options = ParseOptions {
+        max_depth: 0,
+        max_len: 16,
+        max_keys: 2,
+        max_string_len: 3,
+    };
+    assert_eq!(
+        Err(LimitError::TooLong { len: 17, max: 16 }),
+        parse_with_options("a=123456789012345", &options)
+    );
+    assert_eq!(
+        Err(LimitError::TooDeep { offset: 2, max: 0 }),
+        parse_with_options("a={1}", &options)
+    );
+    assert_eq!(
+        Err(LimitError::TooManyKeys { offset: 9, max: 2 }),
+        parse_with_options("a=1,b=2,c=3", &options)
+    );
+    assert_eq!(
+        Err(LimitError::StringTooLong { offset: 2, max: 3 }),
+        parse_with_options("a=\"abcd\"", &options)
+    );
+    assert_eq!(
+        Err(LimitError::StringTooLong { offset: 0, max: 3 }),
+        parse_with_options("abcd=1", &options)
+    );
+    assert!(parse_with_options("abc=\"abc\"", &options).is_ok());
+}
+
+#[test]
+fn deeply_nested_input_test() {
+    let data = format!("a={}1{}", "{".repeat(1_000_000), "}".repeat(1_000_000));
+
+    let options = ParseOptions {
+        max_len: data.len(),
+        ..ParseOptions::default()
+    };
+    assert_eq!(
+        Err(LimitError::TooDeep { offset: 3, max: 1 }),
+        parse_with_options(&data, &options)
+    );
+
+    // without limits, the grammar already stops at the second level
+    assert!(super::tokenize_ast(&data).is_err());
+    assert!(super::parse(&data).is_err());
+    assert!(crate::handwritten::tokenize_ast(&data).is_err());
+    assert!(crate::stream::from_reader(data.as_bytes()).is_err());
+    for map in ["a={x: {", "a={{x: "] {
+        let data = format!("{map}{}", "{x: ".repeat(100_000));
+        assert!(parse_with_options(&data, &ParseOptions::default()).is_err());
+        assert!(super::tokenize_ast(&data).is_err());
+    }
+}
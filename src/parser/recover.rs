@@ -0,0 +1,371 @@
+//! Parser that keeps going after syntax errors, for `acf check` and editors. A broken item
+//! is skipped up to the next `,` or `}`, the rest of the document is still parsed.
+
+use crate::handwritten::{self, Token, TokenKind, Tokenizer};
+
+use super::{Range, RangeMap, ACF};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub span: Range,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}..{}: {}",
+            self.span.start, self.span.end, self.message
+        )
+    }
+}
+
+/// Parses as much of `data` as possible. The result only has the items without errors, and
+/// is the same as [`super::tokenize_ast`] when there are no diagnostics.
+pub fn tokenize_recovering(data: &str) -> (ACF, Vec<Diagnostic>) {
+    let mut parser = Recover {
+        data,
+        tokens: Vec::new(),
+        position: 0,
+        diagnostics: Vec::new(),
+    };
+
+    let mut tokenizer = Tokenizer::recovering(data);
+    loop {
+        let before = tokenizer.offset();
+        match tokenizer.next() {
+            None => break,
+            Some(Ok(token)) => parser.tokens.push(Lexed::Token(token)),
+            Some(Err(error)) => {
+                let rest = &data[before..];
+                let start =
+                    before + rest.len() - rest.trim_start_matches([' ', '\t', '\r', '\n']).len();
+                parser.report(error.offset..tokenizer.offset(), error.kind.to_string());
+                parser.tokens.push(Lexed::Invalid(start));
+            }
+        }
+    }
+
+    let document = parser.document();
+    parser
+        .diagnostics
+        .sort_by_key(|diagnostic| diagnostic.span.start);
+    (document, parser.diagnostics)
+}
+
+enum Lexed {
+    Token(Token),
+    /// Text at this offset the tokenizer rejected, it is already reported.
+    Invalid(usize),
+}
+
+/// The error is in the diagnostics.
+struct Reported;
+
+struct Recover<'a> {
+    data: &'a str,
+    tokens: Vec<Lexed>,
+    position: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Recover<'_> {
+    fn report(&mut self, span: Range, message: impl Into<String>) -> Reported {
+        self.diagnostics.push(Diagnostic {
+            span,
+            message: message.into(),
+        });
+        Reported
+    }
+
+    fn peek(&self) -> Option<&Lexed> {
+        self.tokens.get(self.position)
+    }
+
+    fn peek_kind(&self) -> Option<TokenKind> {
+        match self.peek() {
+            Some(Lexed::Token(token)) => Some(token.kind),
+            _ => None,
+        }
+    }
+
+    fn next(&mut self) -> Option<&Lexed> {
+        let lexed = self.tokens.get(self.position)?;
+        self.position += 1;
+        Some(lexed)
+    }
+
+    /// Takes the next token if `accept` returns true for it, otherwise reports that
+    /// `expected` is missing and leaves the token for recovery.
+    fn expect(
+        &mut self,
+        accept: impl Fn(TokenKind) -> bool,
+        expected: &str,
+    ) -> Result<Token, Reported> {
+        match self.peek() {
+            Some(Lexed::Token(token)) if accept(token.kind) => {
+                let token = token.clone();
+                self.position += 1;
+                Ok(token)
+            }
+            Some(Lexed::Token(token)) => {
+                let message = match token.kind {
+                    TokenKind::BracketOpen if expected.starts_with("a value") => {
+                        "maps and sequences can't be nested".to_string()
+                    }
+                    _ => format!("expected {expected}"),
+                };
+                let span = token.range.clone();
+                Err(self.report(span, message))
+            }
+            Some(Lexed::Invalid(_)) => Err(Reported),
+            None => {
+                let end = self.data.len();
+                Err(self.report(end..end, format!("expected {expected}")))
+            }
+        }
+    }
+
+    /// Skips to the next `,`, or `}` in a composite, jumping over nested braces.
+    fn skip(&mut self, in_composite: bool) {
+        let mut depth = 0;
+        while let Some(lexed) = self.peek() {
+            match lexed {
+                Lexed::Token(token) => match token.kind {
+                    TokenKind::ItemSeperator if depth == 0 => return,
+                    TokenKind::BracketClose if depth == 0 && in_composite => return,
+                    TokenKind::BracketOpen => depth += 1,
+                    TokenKind::BracketClose => depth = usize::max(depth, 1) - 1,
+                    _ => {}
+                },
+                Lexed::Invalid(_) => {}
+            }
+            self.position += 1;
+        }
+    }
+
+    fn document(&mut self) -> ACF {
+        let mut items = Vec::new();
+
+        'items: while self.peek().is_some() {
+            match self.item() {
+                Ok(item) => items.push(item),
+                Err(Reported) => self.skip(false),
+            }
+
+            loop {
+                let Some(lexed) = self.next() else {
+                    break 'items;
+                };
+                match lexed {
+                    Lexed::Token(token) if token.kind == TokenKind::ItemSeperator => {
+                        let span = token.range.clone();
+                        if self.peek().is_none() {
+                            self.report(span, "trailing `,`");
+                        }
+                        continue 'items;
+                    }
+                    Lexed::Token(token) => {
+                        let span = token.range.clone();
+                        self.report(span, "expected `,`");
+                    }
+                    Lexed::Invalid(_) => {}
+                }
+                self.skip(false);
+            }
+        }
+
+        ACF::Map(
+            0..self.data.len(),
+            items.into_iter().collect::<RangeMap<_>>(),
+        )
+    }
+
+    fn item(&mut self) -> Result<(Range, ACF), Reported> {
+        let key = self.expect(is_primative, "a key")?;
+        self.expect(|kind| kind == TokenKind::Assignment, "`=`")?;
+
+        let value = match self.peek_kind() {
+            Some(TokenKind::BracketOpen) => {
+                let open = self.expect(|kind| kind == TokenKind::BracketOpen, "`{`")?;
+                self.composite(open)
+            }
+            _ => self.scalar()?,
+        };
+        Ok((key.range, value))
+    }
+
+    /// Parses the items after `open`, up to and including the `}`.
+    fn composite(&mut self, open: Token) -> ACF {
+        let start = match self.peek() {
+            Some(Lexed::Token(token)) => token.range.start,
+            Some(Lexed::Invalid(offset)) => *offset,
+            None => self.data.len(),
+        };
+        if self.peek_kind() == Some(TokenKind::BracketClose) {
+            self.position += 1;
+            self.report(open.range.start..start + 1, "`{}` needs at least one item");
+            return ACF::Seq(start..start, Vec::new());
+        }
+
+        let is_map = self.peek_kind().is_some_and(is_primative)
+            && matches!(
+                self.tokens.get(self.position + 1),
+                Some(Lexed::Token(Token {
+                    kind: TokenKind::KeySeperator,
+                    ..
+                }))
+            );
+
+        let mut entries = Vec::new();
+        let mut values = Vec::new();
+        let end = 'items: loop {
+            let item = match is_map {
+                true => self.map_item().map(|entry| entries.push(entry)),
+                false => self.scalar().map(|value| values.push(value)),
+            };
+            if item.is_err() {
+                self.skip(true);
+            }
+
+            loop {
+                let Some(lexed) = self.next() else {
+                    self.report(open.range.clone(), "`{` is not closed");
+                    break 'items self.data.len();
+                };
+                match lexed {
+                    Lexed::Token(token) if token.kind == TokenKind::BracketClose => {
+                        break 'items token.range.start;
+                    }
+                    Lexed::Token(token) if token.kind == TokenKind::ItemSeperator => {
+                        let span = token.range.clone();
+                        if self.peek_kind() != Some(TokenKind::BracketClose) {
+                            continue 'items;
+                        }
+                        self.report(span, "trailing `,`");
+                        continue;
+                    }
+                    Lexed::Token(token) => {
+                        let span = token.range.clone();
+                        self.report(span, "expected `,` or `}`");
+                    }
+                    Lexed::Invalid(_) => {}
+                }
+                self.skip(true);
+            }
+        };
+
+        match is_map {
+            true => ACF::Map(start..end, entries.into_iter().collect::<RangeMap<_>>()),
+            false => ACF::Seq(start..end, values),
+        }
+    }
+
+    fn map_item(&mut self) -> Result<(Range, ACF), Reported> {
+        let key = self.expect(is_primative, "a key")?;
+        self.expect(|kind| kind == TokenKind::KeySeperator, "`:`")?;
+        Ok((key.range, self.scalar()?))
+    }
+
+    fn scalar(&mut self) -> Result<ACF, Reported> {
+        let token = self.expect(is_primative, "a value")?;
+        if token.kind != TokenKind::Secret {
+            return Ok(self.primative(token));
+        }
+
+        let value = self.expect(is_primative, "a value after `!secret`")?;
+        let value = self.primative(value);
+        Ok(ACF::Secret(
+            token.range.start..value.as_range().end,
+            Box::new(value),
+        ))
+    }
+
+    fn primative(&self, token: Token) -> ACF {
+        handwritten::primative(self.data, token).expect("checked by expect")
+    }
+}
+
+fn is_primative(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::String | TokenKind::Word | TokenKind::Secret
+    )
+}
+
+#[cfg(test)]
+fn messages(data: &str) -> Vec<(&str, String)> {
+    let (_, diagnostics) = tokenize_recovering(data);
+    diagnostics
+        .into_iter()
+        .map(|diagnostic| (&data[diagnostic.span], diagnostic.message))
+        .collect()
+}
+
+#[test]
+fn recover_test() {
+    let data = "a=1 b, c={x: 1, 2, y: {z}, w: 3}, d=\"\\q\", e={1,}, f={, g=!secret ";
+    let (ast, _) = tokenize_recovering(data);
+
+    assert_eq!(
+        vec![
+            ("b", "expected `,`".to_string()),
+            (",", "expected `:`".to_string()),
+            ("{", "maps and sequences can't be nested".to_string()),
+            ("\\q\"", "invalid escape sequence".to_string()),
+            (",", "trailing `,`".to_string()),
+            ("{", "`{` is not closed".to_string()),
+            (",", "expected a value".to_string()),
+            ("=", "expected `,` or `}`".to_string()),
+        ],
+        messages(data)
+    );
+    assert_eq!(
+        super::parse("a=1, c={x: 1, w: 3}, e={1}, f={g}").unwrap(),
+        crate::tokenized_to_config(data, ast)
+    );
+}
+
+#[test]
+fn recover_errors_test() {
+    assert_eq!(
+        vec![("", "expected a value after `!secret`".to_string())],
+        messages("a=!secret ")
+    );
+    assert_eq!(
+        vec![("{}", "`{}` needs at least one item".to_string())],
+        messages("a={}")
+    );
+    assert_eq!(vec![(",", "trailing `,`".to_string())], messages("a=1,"));
+    assert_eq!(
+        vec![("\"x", "unterminated string".to_string())],
+        messages("a=\"x")
+    );
+    assert_eq!(
+        vec![
+            ("=", "expected a key".to_string()),
+            ("\u{a0}", "unexpected character".to_string())
+        ],
+        messages("=1, b=c\u{a0}")
+    );
+    assert_eq!(
+        vec![(":", "expected `=`".to_string())],
+        messages("a:1, b=2")
+    );
+}
+
+#[test]
+fn recover_matches_tokenize_ast_test() {
+    for data in crate::handwritten::fragment_inputs(20_000) {
+        let (ast, diagnostics) = tokenize_recovering(&data);
+        match super::tokenize_ast(&data) {
+            Ok(expected) => assert_eq!((expected, Vec::new()), (ast, diagnostics), "{data:?}"),
+            Err(_) => {
+                assert!(!diagnostics.is_empty(), "{data:?}");
+                // partial results are still valid
+                crate::tokenized_to_config(&data, ast);
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use super::{Range, RangeMap, ACF};
+
+/// Called while walking a tokenized [`ACF`] in document order, without building the owned tree.
+/// Keys are reported with `visit_key` right before their value.
+#[allow(unused_variables)]
+pub trait Visitor {
+    fn visit_key(&mut self, key: &Range) {}
+
+    fn visit_leaf(&mut self, value: &ACF) {}
+
+    fn enter_map(&mut self, range: &Range, map: &RangeMap<ACF>) {}
+
+    fn leave_map(&mut self, range: &Range, map: &RangeMap<ACF>) {}
+
+    fn enter_seq(&mut self, range: &Range, values: &[ACF]) {}
+
+    fn leave_seq(&mut self, range: &Range, values: &[ACF]) {}
+}
+
+pub fn walk<V: Visitor + ?Sized>(ast: &ACF, visitor: &mut V) {
+    match ast {
+        ACF::Map(range, map) => {
+            visitor.enter_map(range, map);
+            for (key, value) in map.iter() {
+                visitor.visit_key(key);
+                walk(value, visitor);
+            }
+            visitor.leave_map(range, map);
+        }
+        ACF::Seq(range, values) => {
+            visitor.enter_seq(range, values);
+            for value in values.iter() {
+                walk(value, visitor);
+            }
+            visitor.leave_seq(range, values);
+        }
+        leaf => visitor.visit_leaf(leaf),
+    }
+}
+
+#[test]
+fn walk_ast_test() {
+    struct Spans<'a>(&'a str, Vec<&'a str>);
+
+    impl Visitor for Spans<'_> {
+        fn visit_key(&mut self, key: &Range) {
+            self.1.push(&self.0[key.clone()]);
+        }
+
+        fn visit_leaf(&mut self, value: &ACF) {
+            self.1.push(&self.0[value.as_range().clone()]);
+        }
+
+        fn enter_seq(&mut self, _: &Range, _: &[ACF]) {
+            self.1.push("[");
+        }
+
+        fn leave_seq(&mut self, _: &Range, _: &[ACF]) {
+            self.1.push("]");
+        }
+    }
+
+    let data = r#"a=1,b={x, "y"},c={k: !secret v}"#;
+    let ast = super::tokenize_ast(data).unwrap();
+    let mut spans = Spans(data, Vec::new());
+    walk(&ast, &mut spans);
+
+    assert_eq!(
+        vec!["a", "1", "b", "[", "x", "\"y\"", "]", "c", "k", "!secret v"],
+        spans.1
+    );
+}
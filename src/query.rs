@@ -0,0 +1,295 @@
+//! Queries that can match more than one value, using a subset of JSONPath (RFC 9535).
+//!
+//! Supported are child keys (`.name`, `['name']`), indices (`[0]`, `[-1]`), wildcards
+//! (`.*`, `[*]`) and descendant segments (`..name`, `..*`). The leading `$` is optional,
+//! so `services.*.port`, `hosts[*]` and `..timeout` are all valid queries.
+
+use kstring::KStringCow;
+
+use crate::{KeyIndexRef, ACF};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+    Key(String),
+    Index(isize),
+    Wildcard,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    /// Apply the selector to the node and all of its descendants instead of only the node.
+    pub descendant: bool,
+    pub selector: Selector,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    pub segments: Vec<Segment>,
+}
+
+pub type Match<'a> = (Vec<KeyIndexRef<'a>>, &'a ACF);
+
+impl Query {
+    pub fn parse(input: &str) -> Option<Query> {
+        let mut segments = Vec::new();
+        let mut rest = input.strip_prefix('$').unwrap_or(input);
+        let mut first = !input.starts_with('$');
+
+        while !rest.is_empty() {
+            let descendant = match rest.strip_prefix("..") {
+                Some(after) => {
+                    rest = after;
+                    true
+                }
+                None => false,
+            };
+
+            let selector = if let Some(bracket) = rest.strip_prefix('[') {
+                let end = bracket_end(bracket)?;
+                rest = &bracket[end + 1..];
+                parse_bracket(bracket[..end].trim())?
+            } else {
+                if !descendant && !first {
+                    rest = rest.strip_prefix('.')?;
+                }
+                let end = rest.find(['.', '[', ']']).unwrap_or(rest.len());
+                let name = &rest[..end];
+                rest = &rest[end..];
+                match name {
+                    "" => return None,
+                    "*" => Selector::Wildcard,
+                    name => Selector::Key(name.to_string()),
+                }
+            };
+
+            segments.push(Segment {
+                descendant,
+                selector,
+            });
+            first = false;
+        }
+
+        Some(Query { segments })
+    }
+
+    /// Returns every match as the path to the value and the value itself, in document order.
+    pub fn matches<'a>(&self, config: &'a ACF) -> impl Iterator<Item = Match<'a>> {
+        let mut nodes: Vec<Match<'a>> = vec![(Vec::new(), config)];
+
+        for segment in &self.segments {
+            let mut next = Vec::new();
+            for (path, node) in nodes {
+                if segment.descendant {
+                    select_descendants(&segment.selector, path, node, &mut next);
+                } else {
+                    select(&segment.selector, &path, node, &mut next);
+                }
+            }
+            nodes = next;
+        }
+
+        nodes.into_iter()
+    }
+}
+
+fn bracket_end(input: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut chars = input.char_indices();
+
+    while let Some((index, ch)) = chars.next() {
+        match (quote, ch) {
+            (None, ']') => return Some(index),
+            (None, '\'' | '"') => quote = Some(ch),
+            (Some(_), '\\') => {
+                chars.next();
+            }
+            (Some(open), ch) if open == ch => quote = None,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+fn parse_bracket(inner: &str) -> Option<Selector> {
+    if inner == "*" {
+        return Some(Selector::Wildcard);
+    }
+
+    let quote = inner.chars().next()?;
+    if quote == '\'' || quote == '"' {
+        let body = inner.strip_prefix(quote)?.strip_suffix(quote)?;
+        let mut key = String::new();
+        let mut chars = body.chars();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\\' => key.push(chars.next()?),
+                ch if ch == quote => return None,
+                ch => key.push(ch),
+            }
+        }
+        return Some(Selector::Key(key));
+    }
+
+    inner.parse().ok().map(Selector::Index)
+}
+
+fn select<'a>(
+    selector: &Selector,
+    path: &[KeyIndexRef<'a>],
+    node: &'a ACF,
+    out: &mut Vec<Match<'a>>,
+) {
+    let child = |key: KeyIndexRef<'a>| {
+        let mut path = path.to_vec();
+        path.push(key);
+        path
+    };
+
+    match (selector, node) {
+        (Selector::Key(key), ACF::Map(map)) => {
+            if let Some((key, value)) = map.get_key_value(key.as_str()) {
+                out.push((child(KeyIndexRef::String(KStringCow::from_ref(key))), value));
+            }
+        }
+        (Selector::Index(index), ACF::Seq(values)) => {
+            let position = if *index >= 0 {
+                Some(*index as usize)
+            } else {
+                values.len().checked_sub(index.unsigned_abs())
+            };
+            if let Some((position, value)) =
+                position.and_then(|position| Some((position, values.get(position)?)))
+            {
+                out.push((child(KeyIndexRef::Integer(position as isize)), value));
+            }
+        }
+        (Selector::Wildcard, ACF::Map(map)) => {
+            for (key, value) in map.iter() {
+                out.push((child(KeyIndexRef::String(KStringCow::from_ref(key))), value));
+            }
+        }
+        (Selector::Wildcard, ACF::Seq(values)) => {
+            for (index, value) in values.iter().enumerate() {
+                out.push((child(KeyIndexRef::Integer(index as isize)), value));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn select_descendants<'a>(
+    selector: &Selector,
+    path: Vec<KeyIndexRef<'a>>,
+    node: &'a ACF,
+    out: &mut Vec<Match<'a>>,
+) {
+    select(selector, &path, node, out);
+
+    let mut children = Vec::new();
+    select(&Selector::Wildcard, &path, node, &mut children);
+    for (path, child) in children {
+        select_descendants(selector, path, child, out);
+    }
+}
+
+impl ACF {
+    /// Runs a [`Query`], returns `None` when `query` is not valid.
+    pub fn query(&self, query: &str) -> Option<impl Iterator<Item = Match<'_>>> {
+        Some(Query::parse(query)?.matches(self))
+    }
+}
+
+#[cfg(test)]
+fn services() -> ACF {
+    use crate::{acf_map, acf_seq};
+
+    acf_map! {
+        "services" => acf_map! {
+            "web" => acf_map! {"port" => 80, "timeout" => 5},
+            "db" => acf_map! {"port" => 5432, "hosts" => acf_seq!{"a", "b", "c"}},
+        },
+        "timeout" => 30,
+    }
+}
+
+#[cfg(test)]
+fn run(config: &ACF, query: &str) -> Vec<(String, ACF)> {
+    config
+        .query(query)
+        .unwrap()
+        .map(|(path, value)| (crate::selector::to_path(&path), value.clone()))
+        .collect()
+}
+
+#[test]
+fn query_wildcard_test() {
+    let config = services();
+
+    assert_eq!(
+        vec![
+            ("services.web.port".to_string(), ACF::from(80)),
+            ("services.db.port".to_string(), ACF::from(5432)),
+        ],
+        run(&config, "services.*.port")
+    );
+    assert_eq!(
+        run(&config, "services.*.port"),
+        run(&config, "$.services[*]['port']")
+    );
+    assert_eq!(
+        vec![
+            ("services.db.hosts[0]".to_string(), ACF::from("a")),
+            ("services.db.hosts[1]".to_string(), ACF::from("b")),
+            ("services.db.hosts[2]".to_string(), ACF::from("c")),
+        ],
+        run(&config, "services.db.hosts[*]")
+    );
+    assert_eq!(
+        vec![("services.db.hosts[2]".to_string(), ACF::from("c"))],
+        run(&config, "$.services.db.hosts[-1]")
+    );
+}
+
+#[test]
+fn query_descendant_test() {
+    let config = services();
+
+    assert_eq!(
+        vec![
+            ("timeout".to_string(), ACF::from(30)),
+            ("services.web.timeout".to_string(), ACF::from(5)),
+        ],
+        run(&config, "..timeout")
+    );
+    assert_eq!(
+        vec![("services.db.hosts[0]".to_string(), ACF::from("a"))],
+        run(&config, "$..hosts[0]")
+    );
+    assert_eq!(11, run(&config, "$..*").len());
+    assert!(run(&config, "missing.*").is_empty());
+}
+
+#[test]
+fn query_parse_test() {
+    assert_eq!(
+        Some(Query {
+            segments: vec![
+                Segment {
+                    descendant: false,
+                    selector: Selector::Key("a.b".to_string())
+                },
+                Segment {
+                    descendant: true,
+                    selector: Selector::Index(-2)
+                },
+            ]
+        }),
+        Query::parse(r#"$["a.b"]..[-2]"#)
+    );
+    assert_eq!(Some(Query { segments: vec![] }), Query::parse("$"));
+
+    for query in ["a..", "a.", "$a", "a[", "a['b]", "a[x]", "..", "a.b]"] {
+        assert_eq!(None, Query::parse(query), "{query}");
+    }
+}
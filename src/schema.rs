@@ -0,0 +1,709 @@
+//! Describes the expected shape of a config. Schemas are read from an ACF tree or from JSON,
+//! using a subset of JSON Schema: `type`, `properties`, `required`, `additionalProperties`,
+//! `items`, `minimum`, `maximum`, `pattern`, `enum` and `default`. A schema can also be a plain type
+//! name, so `properties={host: string, port: integer}` is a valid ACF schema.
+
+use std::cmp::Ordering;
+
+use kstring::KStringCow;
+use regex::Regex;
+
+use crate::selector::walk_pointer;
+use crate::{KeyIndexRef, Kind, StringMap, ACF};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    String,
+    Integer,
+    /// Either an integer or a float.
+    Number,
+    Boolean,
+    Seq,
+    Map,
+}
+
+impl Type {
+    fn from_name(name: &str) -> Option<Type> {
+        Some(match name {
+            "string" => Type::String,
+            "integer" => Type::Integer,
+            "number" | "float" => Type::Number,
+            "boolean" => Type::Boolean,
+            "array" | "seq" | "sequence" => Type::Seq,
+            "object" | "map" => Type::Map,
+            _ => return None,
+        })
+    }
+
+    fn matches(&self, kind: Kind) -> bool {
+        matches!(
+            (self, kind),
+            (Type::String, Kind::String)
+                | (Type::Integer, Kind::Integer)
+                | (Type::Number, Kind::Integer | Kind::Float)
+                | (Type::Boolean, Kind::Boolean)
+                | (Type::Seq, Kind::Seq)
+                | (Type::Map, Kind::Map)
+        )
+    }
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Type::String => "string",
+            Type::Integer => "integer",
+            Type::Number => "number",
+            Type::Boolean => "boolean",
+            Type::Seq => "sequence",
+            Type::Map => "map",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Schema {
+    pub kind: Option<Type>,
+    pub minimum: Option<Bound>,
+    pub maximum: Option<Bound>,
+    pub pattern: Option<Regex>,
+    pub choices: Vec<ACF>,
+    pub items: Option<Box<Schema>>,
+    pub properties: StringMap<Schema>,
+    pub required: Vec<String>,
+    pub additional_properties: bool,
+    /// Value filled in by [`Schema::apply_defaults`] when the key is missing.
+    pub default: Option<ACF>,
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Schema {
+            kind: None,
+            minimum: None,
+            maximum: None,
+            pattern: None,
+            choices: Vec::new(),
+            items: None,
+            properties: StringMap::default(),
+            required: Vec::new(),
+            additional_properties: true,
+            default: None,
+        }
+    }
+}
+
+/// Value of `minimum` or `maximum`. Integer bounds are kept as integers so they compare exactly
+/// against integer values, which don't all fit in an `f64`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound {
+    Integer(i64),
+    Float(f64),
+}
+
+impl Bound {
+    /// Orders `config` against this bound, `None` for non-numbers and NaN.
+    fn compare(self, config: &ACF) -> Option<Ordering> {
+        match (config, self) {
+            (ACF::Integer(value), Bound::Integer(bound)) => Some(value.cmp(&bound)),
+            (ACF::Integer(value), Bound::Float(bound)) => compare_integer_float(*value, bound),
+            (ACF::Float(value), Bound::Integer(bound)) => {
+                compare_integer_float(bound, value.into_inner()).map(Ordering::reverse)
+            }
+            (ACF::Float(value), Bound::Float(bound)) => value.into_inner().partial_cmp(&bound),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Bound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Bound::Integer(integer) => write!(f, "{integer}"),
+            Bound::Float(float) => write!(f, "{float}"),
+        }
+    }
+}
+
+fn compare_integer_float(integer: i64, float: f64) -> Option<Ordering> {
+    // -2^63 is exact as an f64, 2^63 is the first float past i64::MAX
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+    if float.is_nan() {
+        None
+    } else if float >= LIMIT {
+        Some(Ordering::Less)
+    } else if float < -LIMIT {
+        Some(Ordering::Greater)
+    } else {
+        let truncated = float.trunc();
+        Some(
+            integer
+                .cmp(&(truncated as i64))
+                .then_with(|| 0.0.partial_cmp(&(float - truncated)).unwrap()),
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SchemaError {
+    /// JSON pointer into the schema document.
+    pub path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid schema at '{}': {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ViolationKind {
+    Type { expected: Type, found: Kind },
+    Required,
+    NotAllowed,
+    Minimum(Bound),
+    Maximum(Bound),
+    Pattern(String),
+    Choice,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// JSON pointer to the offending value.
+    pub path: String,
+    pub kind: ViolationKind,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.path)?;
+        match &self.kind {
+            ViolationKind::Type { expected, found } => {
+                write!(f, "expected {expected}, found {found}")
+            }
+            ViolationKind::Required => f.write_str("missing required key"),
+            ViolationKind::NotAllowed => f.write_str("key is not allowed"),
+            ViolationKind::Minimum(minimum) => write!(f, "value is less than {minimum}"),
+            ViolationKind::Maximum(maximum) => write!(f, "value is greater than {maximum}"),
+            ViolationKind::Pattern(pattern) => write!(f, "value does not match '{pattern}'"),
+            ViolationKind::Choice => f.write_str("value is not one of the allowed choices"),
+        }
+    }
+}
+
+impl Schema {
+    pub fn from_acf(config: &ACF) -> Result<Schema, SchemaError> {
+        let mut path = Vec::new();
+        schema_from_acf(config, &mut path)
+    }
+
+    pub fn from_json(value: serde_json::Value) -> Result<Schema, SchemaError> {
+        let config = ACF::try_from(value).map_err(|e| SchemaError {
+            path: String::new(),
+            message: e.to_string(),
+        })?;
+        Schema::from_acf(&config)
+    }
+
+    /// Checks `config` against this schema and returns every violation found.
+    pub fn validate(&self, config: &ACF) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let mut path = Vec::new();
+        self.validate_inner(config, &mut path, &mut violations);
+        violations
+    }
+
+    pub fn is_valid(&self, config: &ACF) -> bool {
+        self.validate(config).is_empty()
+    }
+
+    /// Inserts the declared default of every missing optional key, recursing into nested maps,
+    /// into each element of sequences and into secrets. Required keys are left out, so
+    /// [`Schema::validate`] still reports them. Returns the JSON pointers of the inserted values.
+    pub fn apply_defaults(&self, config: &mut ACF) -> Vec<String> {
+        let mut defaulted = Vec::new();
+        let mut path = Vec::new();
+        self.apply_defaults_inner(config, &mut path, &mut defaulted);
+        defaulted
+    }
+
+    fn apply_defaults_inner(
+        &self,
+        config: &mut ACF,
+        path: &mut Vec<KeyIndexRef<'static>>,
+        defaulted: &mut Vec<String>,
+    ) {
+        let config = match config {
+            ACF::Secret(secret) => secret.expose_secret_mut().as_mut(),
+            config => config,
+        };
+        match config {
+            ACF::Seq(values) => {
+                if let Some(items) = &self.items {
+                    for (index, value) in values.iter_mut().enumerate() {
+                        path.push(KeyIndexRef::Integer(index as isize));
+                        items.apply_defaults_inner(value, path, defaulted);
+                        path.pop();
+                    }
+                }
+            }
+            ACF::Map(map) => {
+                for (key, schema) in self.properties.iter() {
+                    path.push(KeyIndexRef::String(KStringCow::from_string(key.clone())));
+                    match (map.get_mut(key), &schema.default) {
+                        (Some(value), _) => schema.apply_defaults_inner(value, path, defaulted),
+                        (None, Some(default)) if !self.required.contains(key) => {
+                            defaulted.push(walk_pointer(path.iter()));
+                            let value = map.entry(key.clone()).or_insert(default.clone());
+                            schema.apply_defaults_inner(value, path, defaulted);
+                        }
+                        (None, _) => {}
+                    }
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn validate_inner<'a>(
+        &self,
+        config: &'a ACF,
+        path: &mut Vec<KeyIndexRef<'a>>,
+        violations: &mut Vec<Violation>,
+    ) {
+        let config = match config {
+            ACF::Secret(secret) => secret.expose_secret(),
+            config => config,
+        };
+        let mut violation = |path: &Vec<KeyIndexRef<'a>>, kind| {
+            violations.push(Violation {
+                path: walk_pointer(path),
+                kind,
+            })
+        };
+
+        if let Some(expected) = self.kind {
+            if !expected.matches(config.kind()) {
+                violation(
+                    path,
+                    ViolationKind::Type {
+                        expected,
+                        found: config.kind(),
+                    },
+                );
+                return;
+            }
+        }
+
+        if !self.choices.is_empty() && !self.choices.contains(config) {
+            violation(path, ViolationKind::Choice);
+        }
+
+        match (self.minimum, self.maximum) {
+            (Some(minimum), _) if minimum.compare(config) == Some(Ordering::Less) => {
+                violation(path, ViolationKind::Minimum(minimum))
+            }
+            (_, Some(maximum)) if maximum.compare(config) == Some(Ordering::Greater) => {
+                violation(path, ViolationKind::Maximum(maximum))
+            }
+            _ => {}
+        }
+
+        if let (Some(pattern), ACF::String(string)) = (&self.pattern, config) {
+            if !pattern.is_match(string) {
+                violation(path, ViolationKind::Pattern(pattern.as_str().to_string()));
+            }
+        }
+
+        match config {
+            ACF::Seq(values) => {
+                if let Some(items) = &self.items {
+                    for (index, value) in values.iter().enumerate() {
+                        path.push(KeyIndexRef::Integer(index as isize));
+                        items.validate_inner(value, path, violations);
+                        path.pop();
+                    }
+                }
+            }
+            ACF::Map(map) => {
+                for key in &self.required {
+                    if !map.contains_key(key) {
+                        path.push(KeyIndexRef::String(KStringCow::from_string(key.clone())));
+                        violations.push(Violation {
+                            path: walk_pointer(path.iter()),
+                            kind: ViolationKind::Required,
+                        });
+                        path.pop();
+                    }
+                }
+
+                for (key, value) in map.iter() {
+                    path.push(KeyIndexRef::String(KStringCow::from_ref(key)));
+                    match self.properties.get(key) {
+                        Some(schema) => schema.validate_inner(value, path, violations),
+                        None if !self.additional_properties => violations.push(Violation {
+                            path: walk_pointer(path.iter()),
+                            kind: ViolationKind::NotAllowed,
+                        }),
+                        None => {}
+                    }
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn schema_from_acf<'a>(
+    config: &'a ACF,
+    path: &mut Vec<KeyIndexRef<'a>>,
+) -> Result<Schema, SchemaError> {
+    let error = |path: &Vec<KeyIndexRef<'a>>, message: String| SchemaError {
+        path: walk_pointer(path),
+        message,
+    };
+
+    let map = match config {
+        ACF::String(name) => {
+            return match Type::from_name(name) {
+                Some(kind) => Ok(Schema {
+                    kind: Some(kind),
+                    ..Schema::default()
+                }),
+                None => Err(error(path, format!("unknown type '{name}'"))),
+            }
+        }
+        ACF::Map(map) => map,
+        config => {
+            return Err(error(
+                path,
+                format!("expected a type name or map, found {}", config.kind()),
+            ))
+        }
+    };
+
+    let mut schema = Schema::default();
+
+    for (key, value) in map.iter() {
+        path.push(KeyIndexRef::String(KStringCow::from_ref(key)));
+
+        match (key.as_str(), value) {
+            ("type", ACF::String(name)) => {
+                schema.kind = Some(
+                    Type::from_name(name)
+                        .ok_or_else(|| error(path, format!("unknown type '{name}'")))?,
+                );
+            }
+            ("minimum", ACF::Integer(integer)) => schema.minimum = Some(Bound::Integer(*integer)),
+            ("minimum", ACF::Float(float)) => {
+                schema.minimum = Some(Bound::Float(float.into_inner()))
+            }
+            ("maximum", ACF::Integer(integer)) => schema.maximum = Some(Bound::Integer(*integer)),
+            ("maximum", ACF::Float(float)) => {
+                schema.maximum = Some(Bound::Float(float.into_inner()))
+            }
+            ("pattern", ACF::String(pattern)) => {
+                schema.pattern = Some(Regex::new(pattern).map_err(|e| error(path, e.to_string()))?);
+            }
+            ("enum", ACF::Seq(choices)) => schema.choices = choices.clone(),
+            ("items", items) => schema.items = Some(Box::new(schema_from_acf(items, path)?)),
+            ("properties", ACF::Map(properties)) => {
+                for (name, property) in properties.iter() {
+                    path.push(KeyIndexRef::String(KStringCow::from_ref(name)));
+                    schema
+                        .properties
+                        .insert(name.clone(), schema_from_acf(property, path)?);
+                    path.pop();
+                }
+            }
+            ("required", ACF::Seq(required)) => {
+                for name in required {
+                    match name {
+                        ACF::String(name) => schema.required.push(name.to_string()),
+                        name => {
+                            return Err(error(
+                                path,
+                                format!("expected key names, found {}", name.kind()),
+                            ))
+                        }
+                    }
+                }
+            }
+            ("default", default) => schema.default = Some(default.clone()),
+            ("additionalProperties", ACF::Boolean(allowed)) => {
+                schema.additional_properties = *allowed;
+            }
+            (
+                "type"
+                | "minimum"
+                | "maximum"
+                | "pattern"
+                | "enum"
+                | "properties"
+                | "required"
+                | "additionalProperties",
+                value,
+            ) => {
+                return Err(error(
+                    path,
+                    format!("unexpected {} for '{key}'", value.kind()),
+                ))
+            }
+            // other JSON Schema keywords such as `title` or `$schema` are ignored
+            _ => {}
+        }
+
+        path.pop();
+    }
+
+    Ok(schema)
+}
+
+#[cfg(test)]
+fn server_schema() -> Schema {
+    Schema::from_json(serde_json::json!({
+        "type": "object",
+        "required": ["server", "mode"],
+        "additionalProperties": false,
+        "properties": {
+            "server": {
+                "type": "object",
+                "required": ["host", "port"],
+                "properties": {
+                    "host": {"type": "string", "pattern": "^[a-z.]+$"},
+                    "port": {"type": "integer", "minimum": 1, "maximum": 65535},
+                },
+            },
+            "mode": {"enum": ["fast", "slow"]},
+            "tags": {"type": "array", "items": "string"},
+        },
+    }))
+    .unwrap()
+}
+
+#[test]
+fn schema_valid_test() {
+    use crate::{acf_map, acf_seq};
+
+    let config = acf_map! {
+        "server" => acf_map! {
+            "host" => "example.com",
+            "port" => 8080,
+        },
+        "mode" => "fast",
+        "tags" => acf_seq!{"a", "b"},
+    };
+
+    assert_eq!(Vec::<Violation>::new(), server_schema().validate(&config));
+}
+
+#[test]
+fn schema_all_violations_test() {
+    use crate::{acf_map, acf_seq};
+
+    let config = acf_map! {
+        "server" => acf_map! {
+            "host" => "Example.com",
+            "port" => 0,
+        },
+        "mode" => "medium",
+        "tags" => acf_seq!{"a", 1},
+        "extra" => true,
+    };
+
+    let violations: Vec<String> = server_schema()
+        .validate(&config)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+    assert_eq!(
+        vec![
+            "/server/host: value does not match '^[a-z.]+$'",
+            "/server/port: value is less than 1",
+            "/mode: value is not one of the allowed choices",
+            "/tags/1: expected string, found integer",
+            "/extra: key is not allowed",
+        ],
+        violations
+    );
+
+    let violations = server_schema().validate(&acf_map! {"server" => 1});
+    assert_eq!(
+        vec![
+            Violation {
+                path: "/mode".to_string(),
+                kind: ViolationKind::Required
+            },
+            Violation {
+                path: "/server".to_string(),
+                kind: ViolationKind::Type {
+                    expected: Type::Map,
+                    found: Kind::Integer
+                }
+            },
+        ],
+        violations
+    );
+}
+
+#[test]
+fn schema_nested_test() {
+    use crate::{acf_map, acf_seq};
+
+    let schema = Schema::from_json(serde_json::json!({
+        "properties": {
+            "a": {"properties": {"b": {"items": {"properties": {"c": {"items": "integer"}}}}}},
+        },
+    }))
+    .unwrap();
+
+    let config = acf_map! {
+        "a" => acf_map! {"b" => acf_seq!{acf_map! {"c" => acf_seq!{1, "2"}}}},
+    };
+    let violations: Vec<String> = schema
+        .validate(&config)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        vec!["/a/b/0/c/1: expected integer, found string"],
+        violations
+    );
+}
+
+#[test]
+fn schema_bounds_test() {
+    // 2^53 + 1 is not representable as an f64
+    let schema = Schema::from_json(serde_json::json!({
+        "minimum": -9007199254740993_i64,
+        "maximum": 9007199254740993_i64,
+    }))
+    .unwrap();
+
+    assert!(schema.is_valid(&ACF::Integer(9007199254740993)));
+    assert!(schema.is_valid(&ACF::Integer(-9007199254740993)));
+    assert_eq!(
+        vec![ViolationKind::Maximum(Bound::Integer(9007199254740993))],
+        schema
+            .validate(&ACF::Integer(9007199254740994))
+            .into_iter()
+            .map(|violation| violation.kind)
+            .collect::<Vec<_>>()
+    );
+    assert!(!schema.is_valid(&ACF::Integer(-9007199254740994)));
+    assert!(schema.is_valid(&ACF::Float(1.5.into())));
+
+    let schema = Schema::from_json(serde_json::json!({"minimum": 1.5, "maximum": 1e300})).unwrap();
+    assert!(!schema.is_valid(&ACF::Integer(1)));
+    assert!(schema.is_valid(&ACF::Integer(2)));
+    assert!(schema.is_valid(&ACF::Integer(i64::MAX)));
+    assert!(!schema.is_valid(&ACF::Float(1e301.into())));
+}
+
+#[test]
+fn schema_from_acf_test() {
+    let data = "type=map,required={host},properties={host: string, port: integer}";
+    let tokens = crate::parser::tokenize_ast(data).unwrap();
+    let schema = Schema::from_acf(&crate::tokenized_to_config(data, tokens)).unwrap();
+
+    assert!(schema.is_valid(&crate::acf_map! {"host" => "a", "port" => 1}));
+    assert_eq!(2, schema.validate(&crate::acf_map! {"port" => "1"}).len());
+
+    let error = Schema::from_json(serde_json::json!({"properties": {"a": {"type": "text"}}}));
+    assert_eq!(
+        Err(SchemaError {
+            path: "/properties/a/type".to_string(),
+            message: "unknown type 'text'".to_string()
+        }),
+        error.map(|_| ())
+    );
+}
+
+#[test]
+fn schema_apply_defaults_test() {
+    use crate::{acf_map, acf_seq};
+
+    let schema = Schema::from_json(serde_json::json!({
+        "properties": {
+            "server": {
+                "default": {},
+                "properties": {
+                    "host": {"type": "string", "default": "localhost"},
+                    "port": {"type": "integer", "default": 8080},
+                },
+            },
+            "workers": {
+                "items": {
+                    "properties": {
+                        "threads": {"default": 4},
+                    },
+                },
+            },
+            "name": {"type": "string"},
+        },
+    }))
+    .unwrap();
+
+    let mut config = acf_map! {
+        "server" => acf_map! {"port" => 9000},
+        "workers" => acf_seq!{acf_map!{"threads" => 1}, acf_map!{"name" => "b"}},
+    };
+
+    let defaulted = schema.apply_defaults(&mut config);
+
+    assert_eq!(vec!["/server/host", "/workers/1/threads"], defaulted);
+    assert_eq!(
+        acf_map! {
+            "server" => acf_map! {"port" => 9000, "host" => "localhost"},
+            "workers" => acf_seq!{
+                acf_map!{"threads" => 1},
+                acf_map!{"name" => "b", "threads" => 4}
+            },
+        },
+        config
+    );
+
+    let mut config = ACF::Map(StringMap::default());
+    assert_eq!(
+        vec!["/server", "/server/host", "/server/port"],
+        schema.apply_defaults(&mut config)
+    );
+    assert_eq!(Some(&ACF::from(8080)), config.json_pointer("/server/port"));
+}
+
+#[test]
+fn schema_apply_defaults_required_and_secret_test() {
+    use crate::acf_map;
+    use crate::secret::Secret;
+
+    let schema = Schema::from_json(serde_json::json!({
+        "required": ["host"],
+        "properties": {
+            "host": {"type": "string", "default": "localhost"},
+            "db": {
+                "properties": {"port": {"type": "integer", "default": 5432}},
+            },
+        },
+    }))
+    .unwrap();
+
+    let mut config = acf_map! {"db" => Secret::new(acf_map! {"user" => "admin"})};
+    assert_eq!(vec!["/db/port"], schema.apply_defaults(&mut config));
+    assert_eq!(
+        acf_map! {"db" => Secret::new(acf_map! {"user" => "admin", "port" => 5432})},
+        config
+    );
+    assert_eq!(
+        vec![Violation {
+            path: "/host".to_string(),
+            kind: ViolationKind::Required
+        }],
+        schema.validate(&config)
+    );
+}
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Deserializer};
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Holds a value that must not end up in logs. `Debug` and `Display` print `[REDACTED]`,
+/// the value itself is only reachable through [`Secret::expose_secret`] and
+/// [`Secret::expose_secret_mut`].
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret<T>(T);
+
+impl<T> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Secret(value)
+    }
+
+    pub fn expose_secret(&self) -> &T {
+        &self.0
+    }
+
+    pub fn expose_secret_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+
+    pub fn into_exposed(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Secret(value)
+    }
+}
+
+impl<T> std::fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Secret")
+            .field(&format_args!("{REDACTED}"))
+            .finish()
+    }
+}
+
+impl<T> std::fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Secret)
+    }
+}
+
+#[test]
+fn secret_redacted_test() {
+    let secret = Secret::new("hunter2");
+
+    assert_eq!("Secret([REDACTED])", format!("{secret:?}"));
+    assert_eq!("[REDACTED]", format!("{secret}"));
+    assert_eq!(&"hunter2", secret.expose_secret());
+}
@@ -1,4 +1,4 @@
-use crate::ACF;
+use crate::{Kind, ACF};
 use kstring::KStringCow;
 
 // pub enum KeyIndex {
@@ -44,7 +44,173 @@ pub fn parse_json_pointer<'a, I: FromIterator<KeyIndexRef<'a>>>(input: &'a str)
     Some(iterator.collect())
 }
 
-#[derive(Debug, PartialEq)]
+/// Parses dot/bracket paths such as `db.hosts[-1].name`. Keys containing special
+/// characters can be quoted, either as a segment (`db."a.b"`) or in brackets (`db["a.b"]`).
+/// Bracketed integers are indices and may be negative to count from the end.
+pub fn parse_path<'a, I: FromIterator<KeyIndexRef<'a>>>(input: &'a str) -> Option<I> {
+    let mut out = Vec::new();
+    let mut rest = input;
+    let mut first = true;
+
+    while !rest.is_empty() {
+        if let Some(bracket) = rest.strip_prefix('[') {
+            if bracket.starts_with('"') {
+                let (key, after) = quoted_key(bracket)?;
+                out.push(KeyIndexRef::String(key));
+                rest = after.strip_prefix(']')?;
+            } else {
+                let end = bracket.find(']')?;
+                out.push(KeyIndexRef::Integer(bracket[..end].parse().ok()?));
+                rest = &bracket[end + 1..];
+            }
+        } else {
+            if !first {
+                rest = rest.strip_prefix('.')?;
+            }
+            if rest.starts_with('"') {
+                let (key, after) = quoted_key(rest)?;
+                out.push(KeyIndexRef::String(key));
+                rest = after;
+            } else {
+                let end = rest
+                    .find(|ch| PATH_SPECIAL_CHARS.contains(&ch))
+                    .unwrap_or(rest.len());
+                if end == 0 {
+                    return None;
+                }
+                out.push(KeyIndexRef::String(KStringCow::from_ref(&rest[..end])));
+                rest = &rest[end..];
+            }
+        }
+        first = false;
+    }
+
+    Some(out.into_iter().collect())
+}
+
+const PATH_SPECIAL_CHARS: [char; 5] = ['.', '[', ']', '"', '\\'];
+
+/// Reads a `"` quoted key at the start of `input`, returning the key and the remaining input.
+fn quoted_key(input: &str) -> Option<(KStringCow<'_>, &str)> {
+    let inner = input.strip_prefix('"')?;
+    let mut key = String::new();
+    let mut chars = inner.char_indices();
+
+    while let Some((index, ch)) = chars.next() {
+        match ch {
+            '"' => {
+                let key = if key.len() == index {
+                    KStringCow::from_ref(&inner[..index])
+                } else {
+                    KStringCow::from_string(key)
+                };
+                return Some((key, &inner[index + 1..]));
+            }
+            '\\' => key.push(chars.next()?.1),
+            ch => key.push(ch),
+        }
+    }
+
+    None
+}
+
+/// Writes `path` in the syntax read by [`parse_path`].
+pub fn to_path<'a, 'b: 'a, I: IntoIterator<Item = &'a KeyIndexRef<'b>>>(path: I) -> String {
+    let mut out = String::new();
+
+    for key in path {
+        match key {
+            KeyIndexRef::String(key) => {
+                if !out.is_empty() {
+                    out.push('.');
+                }
+                if key.is_empty() || key.contains(PATH_SPECIAL_CHARS) {
+                    out.push('"');
+                    for ch in key.chars() {
+                        if ch == '"' || ch == '\\' {
+                            out.push('\\');
+                        }
+                        out.push(ch);
+                    }
+                    out.push('"');
+                } else {
+                    out.push_str(key);
+                }
+            }
+            KeyIndexRef::Integer(index) => {
+                out.push('[');
+                out.push_str(&index.to_string());
+                out.push(']');
+            }
+        }
+    }
+
+    out
+}
+
+/// Writes `path` as a JSON pointer. JSON pointers have no negative indices, so this is `None`
+/// for a path with one; [`resolve_path`] turns them into positions first.
+pub fn to_json_pointer<'a, 'b: 'a, I: IntoIterator<Item = &'a KeyIndexRef<'b>>>(
+    path: I,
+) -> Option<String> {
+    let path: Vec<_> = path.into_iter().collect();
+    if path
+        .iter()
+        .any(|key| matches!(key, KeyIndexRef::Integer(index) if *index < 0))
+    {
+        return None;
+    }
+    Some(walk_pointer(path))
+}
+
+/// [`to_json_pointer`] for paths built while walking a config, which have no negative indices.
+pub(crate) fn walk_pointer<'a, 'b: 'a, I: IntoIterator<Item = &'a KeyIndexRef<'b>>>(
+    path: I,
+) -> String {
+    let mut out = String::new();
+
+    for key in path {
+        out.push('/');
+        match key {
+            KeyIndexRef::String(key) => out.push_str(&key.replace('~', "~0").replace('/', "~1")),
+            KeyIndexRef::Integer(index) => {
+                debug_assert!(*index >= 0, "negative index in a walked path");
+                out.push_str(&index.to_string())
+            }
+        }
+    }
+
+    out
+}
+
+/// Replaces the negative indices of `path` with their position in `config`. `None` if `path`
+/// doesn't select a value.
+pub fn resolve_path<'a>(config: &ACF, path: &[KeyIndexRef<'a>]) -> Option<Vec<KeyIndexRef<'a>>> {
+    let mut config_pointer = config;
+    let mut out = Vec::with_capacity(path.len());
+
+    for key in path {
+        match (key, config_pointer) {
+            (KeyIndexRef::Integer(index), ACF::Seq(values)) => {
+                config_pointer = config_pointer.get_index(*index)?;
+                let position = match *index < 0 {
+                    true => index + values.len() as isize,
+                    false => *index,
+                };
+                out.push(KeyIndexRef::Integer(position));
+            }
+            (KeyIndexRef::String(name), _) => {
+                config_pointer = config_pointer.get(name)?;
+                out.push(key.clone());
+            }
+            (KeyIndexRef::Integer(_), _) => return None,
+        }
+    }
+
+    Some(out)
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum KeyIndexRef<'a> {
     String(KStringCow<'a>),
     Integer(isize),
@@ -82,6 +248,143 @@ pub fn selector<'a, 'b, I: IntoIterator<Item = &'b KeyIndexRef<'b>>>(
     Some(config_pointer)
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectorErrorKind {
+    MissingKey {
+        key: String,
+        /// Existing keys close to `key`, closest first.
+        suggestions: Vec<String>,
+    },
+    IndexOutOfBounds {
+        index: isize,
+        len: usize,
+    },
+    /// A key was applied to something that is not a map, or an index to something that is not a sequence.
+    WrongType {
+        expected: Kind,
+        found: Kind,
+    },
+    InvalidPointer,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectorError {
+    /// Position of the segment that missed.
+    pub segment: usize,
+    /// JSON pointer to the value the segment was applied to.
+    pub at: String,
+    pub kind: SelectorErrorKind,
+}
+
+impl std::fmt::Display for SelectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let at = if self.at.is_empty() { "/" } else { &self.at };
+
+        match &self.kind {
+            SelectorErrorKind::MissingKey { key, suggestions } => {
+                write!(f, "no key '{key}' at {at}")?;
+                if !suggestions.is_empty() {
+                    write!(f, ", did you mean '{}'?", suggestions.join("', '"))?;
+                }
+                Ok(())
+            }
+            SelectorErrorKind::IndexOutOfBounds { index, len } => {
+                write!(f, "index {index} is out of bounds at {at}, length is {len}")
+            }
+            SelectorErrorKind::WrongType { expected, found } => {
+                write!(f, "expected {expected} at {at}, found {found}")
+            }
+            SelectorErrorKind::InvalidPointer => f.write_str("invalid JSON pointer"),
+        }
+    }
+}
+
+impl std::error::Error for SelectorError {}
+
+/// Like [`selector`], but explains which segment failed to match.
+pub fn try_selector<'a, 'b, 'c: 'b, I: IntoIterator<Item = &'b KeyIndexRef<'c>>>(
+    config: &'a ACF,
+    selector: I,
+) -> Result<&'a ACF, SelectorError> {
+    let mut config_pointer = config;
+    // negative indices are resolved, so the path can be written as a JSON pointer
+    let mut path: Vec<KeyIndexRef> = Vec::new();
+
+    for (segment, key) in selector.into_iter().enumerate() {
+        let resolved = match (key, config_pointer) {
+            (KeyIndexRef::Integer(index), ACF::Seq(values)) if *index < 0 => {
+                KeyIndexRef::Integer(index + values.len() as isize)
+            }
+            (key, _) => key.clone(),
+        };
+        let next = match (key, config_pointer) {
+            (KeyIndexRef::String(key), ACF::Map(map)) => {
+                map.get(key.as_str())
+                    .ok_or_else(|| SelectorErrorKind::MissingKey {
+                        key: key.to_string(),
+                        suggestions: suggestions(key, map.keys()),
+                    })
+            }
+            (KeyIndexRef::Integer(index), ACF::Seq(values)) => config_pointer
+                .get_index(*index)
+                .ok_or(SelectorErrorKind::IndexOutOfBounds {
+                    index: *index,
+                    len: values.len(),
+                }),
+            (KeyIndexRef::String(_), found) => Err(SelectorErrorKind::WrongType {
+                expected: Kind::Map,
+                found: found.kind(),
+            }),
+            (KeyIndexRef::Integer(_), found) => Err(SelectorErrorKind::WrongType {
+                expected: Kind::Seq,
+                found: found.kind(),
+            }),
+        };
+
+        config_pointer = next.map_err(|kind| SelectorError {
+            segment,
+            at: walk_pointer(&path),
+            kind,
+        })?;
+        path.push(resolved);
+    }
+
+    Ok(config_pointer)
+}
+
+fn suggestions<'a, I: Iterator<Item = &'a String>>(key: &str, candidates: I) -> Vec<String> {
+    let max_distance = key.chars().count().div_ceil(3);
+    let mut found: Vec<(usize, &String)> = candidates
+        .map(|candidate| (edit_distance(key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+
+    found.sort_by_key(|(distance, _)| *distance);
+    found
+        .into_iter()
+        .take(3)
+        .map(|(_, candidate)| candidate.clone())
+        .collect()
+}
+
+/// Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
 #[test]
 fn selector_test() {
     use crate::{acf_map, acf_seq};
@@ -186,3 +489,140 @@ fn parse_json_pointer_invalid_test() {
     let out: Option<Vec<_>> = parse_json_pointer("abcdefg");
     assert!(out.is_none());
 }
+
+#[test]
+fn to_json_pointer_test() {
+    let path: Vec<KeyIndexRef> = vec!["a/b".into(), "c~d".into(), 0.into()];
+
+    assert_eq!(Some("/a~1b/c~0d/0".to_string()), to_json_pointer(&path));
+    assert_eq!(
+        Some(path.clone()),
+        parse_json_pointer(&to_json_pointer(&path).unwrap())
+    );
+    assert_eq!(Some(String::new()), to_json_pointer(&[]));
+}
+
+#[test]
+fn parse_path_test() {
+    let expected: Vec<KeyIndexRef> = vec!["db".into(), "hosts".into(), (-1).into(), "name".into()];
+    assert_eq!(Some(expected), parse_path("db.hosts[-1].name"));
+
+    let expected: Vec<KeyIndexRef> = vec![0.into(), "a.b".into(), "c\"d".into(), "e".into()];
+    assert_eq!(Some(expected), parse_path(r#"[0]."a.b"["c\"d"].e"#));
+
+    assert_eq!(Some(Vec::<KeyIndexRef>::new()), parse_path(""));
+}
+
+#[test]
+fn parse_path_invalid_test() {
+    for path in ["a..b", "a.", ".a", "a[x]", "a[1", r#"a."b"#, "a[0]b", "a]"] {
+        assert_eq!(None, parse_path::<Vec<_>>(path), "{path}");
+    }
+}
+
+#[test]
+fn to_path_test() {
+    let path: Vec<KeyIndexRef> = vec!["db".into(), "a.b".into(), (-1).into(), "".into()];
+    let text = to_path(&path);
+
+    assert_eq!(r#"db."a.b"[-1]."""#, text);
+    assert_eq!(Some(path.clone()), parse_path(&text));
+    assert_eq!(None, to_json_pointer(&path));
+}
+
+#[test]
+fn resolve_path_test() {
+    use crate::{acf_map, acf_seq};
+
+    let config = acf_map! {"db" => acf_map! {"a.b" => acf_seq!{1, acf_map!{"" => 2}}}};
+    let path: Vec<_> = parse_path(r#"db."a.b"[-1]."""#).unwrap();
+    let resolved = resolve_path(&config, &path).unwrap();
+
+    assert_eq!(Some("/db/a.b/1/".to_string()), to_json_pointer(&resolved));
+    assert_eq!(
+        selector(&config, &path),
+        selector(
+            &config,
+            &parse_json_pointer::<Vec<_>>("/db/a.b/1/").unwrap()
+        )
+    );
+    assert_eq!(
+        None,
+        resolve_path(&config, &parse_path::<Vec<_>>("db.a[-3]").unwrap())
+    );
+    assert_eq!(
+        None,
+        resolve_path(&config, &parse_path::<Vec<_>>("db[0]").unwrap())
+    );
+}
+
+#[test]
+fn selector_with_path_test() {
+    use crate::{acf_map, acf_seq};
+
+    let config = acf_map! {
+        "config3" => acf_seq!{false, 123, acf_map!{"a.b" => 1.23}}
+    };
+
+    let path: Vec<_> = parse_path(r#"config3[-1]."a.b""#).unwrap();
+    assert_eq!(&ACF::from(1.23), selector(&config, &path).unwrap());
+    let path: Vec<_> = parse_path("config3[-3]").unwrap();
+    assert_eq!(&ACF::from(false), selector(&config, &path).unwrap());
+}
+
+#[test]
+fn try_selector_test() {
+    use crate::{acf_map, acf_seq};
+
+    let config = acf_map! {
+        "db" => acf_map! {
+            "hosts" => acf_seq!{"a", "b"},
+            "port" => 5432,
+            "posts" => 1,
+        },
+    };
+
+    let path: Vec<_> = parse_path("db.hosts[-1]").unwrap();
+    assert_eq!(Ok(&ACF::from("b")), try_selector(&config, &path));
+
+    let path: Vec<_> = parse_path("db.hosts[-1].name").unwrap();
+    assert_eq!(
+        "expected map at /db/hosts/1, found string",
+        try_selector(&config, &path).unwrap_err().to_string()
+    );
+
+    let path: Vec<_> = parse_path("db.port.number").unwrap();
+    let error = try_selector(&config, &path).unwrap_err();
+    assert_eq!(2, error.segment);
+    assert_eq!("expected map at /db/port, found integer", error.to_string());
+
+    let path: Vec<_> = parse_path("db.hots[0]").unwrap();
+    assert_eq!(
+        "no key 'hots' at /db, did you mean 'hosts', 'posts'?",
+        try_selector(&config, &path).unwrap_err().to_string()
+    );
+
+    let path: Vec<_> = parse_path("database").unwrap();
+    assert_eq!(
+        "no key 'database' at /",
+        try_selector(&config, &path).unwrap_err().to_string()
+    );
+
+    let path: Vec<_> = parse_path("db.hosts[2]").unwrap();
+    assert_eq!(
+        SelectorError {
+            segment: 2,
+            at: "/db/hosts".to_string(),
+            kind: SelectorErrorKind::IndexOutOfBounds { index: 2, len: 2 }
+        },
+        try_selector(&config, &path).unwrap_err()
+    );
+}
+
+#[test]
+fn edit_distance_test() {
+    assert_eq!(0, edit_distance("hosts", "hosts"));
+    assert_eq!(1, edit_distance("hots", "hosts"));
+    assert_eq!(3, edit_distance("kitten", "sitting"));
+    assert_eq!(4, edit_distance("", "port"));
+}
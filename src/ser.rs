@@ -0,0 +1,326 @@
+use std::fmt::{self, Write};
+
+use crate::parser::{parse_float, parse_integer, SPECIAL_CHARS};
+use crate::selector::walk_pointer;
+use crate::{KeyIndexRef, ACF};
+
+/// A value that has no textual form, `pointer` is its JSON pointer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SerializeError {
+    /// A map or sequence inside another one, or anything but a scalar inside a revealed
+    /// secret. The grammar only allows scalars there.
+    Nested { pointer: String },
+    /// NaN can't be written as a float that is read back as one.
+    NaN { pointer: String },
+}
+
+impl fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerializeError::Nested { pointer } => {
+                write!(f, "{pointer}: maps and sequences can't be nested")
+            }
+            SerializeError::NaN { pointer } => write!(f, "{pointer}: NaN can't be written"),
+        }
+    }
+}
+
+impl std::error::Error for SerializeError {}
+
+/// Writes an [`ACF`] back into its textual form. A top level map is written as
+/// `key=value,...`, nested maps as `{key: value}` and sequences as `{value, ...}`.
+#[derive(Debug, Default, Clone)]
+pub struct Serializer {
+    reveal_secrets: bool,
+}
+
+impl Serializer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write the actual value of `!secret` values instead of a placeholder.
+    pub fn reveal_secrets(mut self, reveal: bool) -> Self {
+        self.reveal_secrets = reveal;
+        self
+    }
+
+    pub fn to_string(&self, config: &ACF) -> Result<String, SerializeError> {
+        self.check(config)?;
+
+        let mut out = String::new();
+        self.write(&mut out, config)
+            .expect("writing to a string does not fail");
+        Ok(out)
+    }
+
+    /// Whether `config` can be written in a form that is read back as the same value.
+    pub fn check(&self, config: &ACF) -> Result<(), SerializeError> {
+        let mut path = Vec::new();
+        match config {
+            ACF::Map(map) => map.iter().try_for_each(|(key, value)| {
+                path.push(KeyIndexRef::from(key.as_str()));
+                self.check_value(value, &mut path, false)?;
+                path.pop();
+                Ok(())
+            }),
+            value => self.check_value(value, &mut path, false),
+        }
+    }
+
+    /// `nested` is true for the values of a map or sequence, which have to be scalars.
+    fn check_value<'a>(
+        &self,
+        config: &'a ACF,
+        path: &mut Vec<KeyIndexRef<'a>>,
+        nested: bool,
+    ) -> Result<(), SerializeError> {
+        let pointer = |path: &[KeyIndexRef]| walk_pointer(path);
+        match config {
+            ACF::Float(float) if float.is_nan() => Err(SerializeError::NaN {
+                pointer: pointer(path),
+            }),
+            ACF::Secret(secret) if self.reveal_secrets => {
+                self.check_value(secret.expose_secret(), path, true)
+            }
+            ACF::Seq(_) | ACF::Map(_) if nested => Err(SerializeError::Nested {
+                pointer: pointer(path),
+            }),
+            ACF::Seq(values) => values.iter().enumerate().try_for_each(|(index, value)| {
+                path.push(KeyIndexRef::Integer(index as isize));
+                self.check_value(value, path, true)?;
+                path.pop();
+                Ok(())
+            }),
+            ACF::Map(map) => map.iter().try_for_each(|(key, value)| {
+                path.push(KeyIndexRef::from(key.as_str()));
+                self.check_value(value, path, true)?;
+                path.pop();
+                Ok(())
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes values [`Serializer::check`] rejects as well, nested maps and sequences in
+    /// nested braces and NaN as `NaN`, which the parser doesn't read back. Only fails if `out`
+    /// does.
+    pub fn write<W: Write>(&self, out: &mut W, config: &ACF) -> fmt::Result {
+        match config {
+            ACF::Map(map) => {
+                for (index, (key, value)) in map.iter().enumerate() {
+                    if index > 0 {
+                        out.write_char(',')?;
+                    }
+                    write_string(out, key)?;
+                    out.write_char('=')?;
+                    self.write_value(out, value)?;
+                }
+                Ok(())
+            }
+            value => self.write_value(out, value),
+        }
+    }
+
+    fn write_value<W: Write>(&self, out: &mut W, config: &ACF) -> fmt::Result {
+        match config {
+            ACF::String(string) => write_string(out, string),
+            ACF::Integer(integer) => write!(out, "{integer}"),
+            ACF::Float(float) => write_float(out, float.into_inner()),
+            ACF::Boolean(boolean) => write!(out, "{boolean}"),
+            ACF::Secret(secret) if self.reveal_secrets => {
+                out.write_str("!secret ")?;
+                self.write_value(out, secret.expose_secret())
+            }
+            ACF::Secret(secret) => write!(out, "!secret \"{secret}\""),
+            ACF::Seq(values) => {
+                out.write_char('{')?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        out.write_str(", ")?;
+                    }
+                    self.write_value(out, value)?;
+                }
+                out.write_char('}')
+            }
+            ACF::Map(map) => {
+                out.write_char('{')?;
+                for (index, (key, value)) in map.iter().enumerate() {
+                    if index > 0 {
+                        out.write_str(", ")?;
+                    }
+                    write_string(out, key)?;
+                    out.write_str(": ")?;
+                    self.write_value(out, value)?;
+                }
+                out.write_char('}')
+            }
+        }
+    }
+}
+
+/// Serializes `config` with secrets redacted.
+pub fn to_string(config: &ACF) -> Result<String, SerializeError> {
+    Serializer::new().to_string(config)
+}
+
+/// Writes with secrets redacted, see [`Serializer::write`] for values that can't be read back.
+impl fmt::Display for ACF {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Serializer::new().write(f, self)
+    }
+}
+
+/// Whether `input` has to be quoted to be read back as the same string.
+pub fn needs_quotes(input: &str) -> bool {
+    input.is_empty()
+        || input
+            .chars()
+            .any(|ch| ch.is_whitespace() || SPECIAL_CHARS.contains(&ch) || ch == '"' || ch == '\'')
+        || input.starts_with("@file(")
+        || matches!(input, "true" | "false")
+        || parse_integer(input).is_ok()
+        || parse_float(input).is_ok()
+}
+
+pub fn write_string<W: Write>(out: &mut W, input: &str) -> fmt::Result {
+    if !needs_quotes(input) {
+        return out.write_str(input);
+    }
+    write_quoted(out, input)
+}
+
+/// Writes `input` in quotes, even if it doesn't need them.
+pub(crate) fn write_quoted<W: Write>(out: &mut W, input: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for ch in input.chars() {
+        match ch {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            '\u{08}' => out.write_str("\\b")?,
+            '\u{0C}' => out.write_str("\\f")?,
+            ch => out.write_char(ch)?,
+        }
+    }
+    out.write_char('"')
+}
+
+fn write_float<W: Write>(out: &mut W, float: f64) -> fmt::Result {
+    if float.is_infinite() {
+        // too large for a float, which the parser reads as infinity
+        out.write_str(if float > 0.0 { "1e400" } else { "-1e400" })
+    } else {
+        // debug formatting keeps the `.0` so the value is read back as a float
+        write!(out, "{float:?}")
+    }
+}
+
+#[cfg(test)]
+fn roundtrip(config: &ACF) -> ACF {
+    let text = Serializer::new()
+        .reveal_secrets(true)
+        .to_string(config)
+        .unwrap();
+    let tokens = crate::parser::tokenize_ast(&text).unwrap();
+    crate::tokenized_to_config(&text, tokens)
+}
+
+#[test]
+fn serialize_test() {
+    use crate::{acf_map, acf_seq};
+
+    let config = acf_map! {
+        "config1" => acf_map! {
+            "value" => 1,
+            "default" => 1.0,
+            "yes" => true,
+        },
+        "config2" => acf_map! {
+            "DEFAULT" => "testing",
+            "extra" => "extra \"quotes\", and 'more'",
+        },
+        "config3" => acf_seq!{false, "123", 1e100, "", "tab\there"}
+    };
+
+    assert_eq!(
+        r#"config1={value: 1, default: 1.0, yes: true},config2={DEFAULT: testing, extra: "extra \"quotes\", and 'more'"},config3={false, "123", 1e100, "", "tab\there"}"#,
+        config.to_string()
+    );
+    assert_eq!(config, roundtrip(&config));
+}
+
+#[test]
+fn serialize_secret_test() {
+    use crate::acf_map;
+    use crate::secret::Secret;
+
+    let config = acf_map! {
+        "user" => "admin",
+        "password" => Secret::new("hunter2"),
+    };
+
+    assert_eq!(
+        r#"user=admin,password=!secret "[REDACTED]""#,
+        config.to_string()
+    );
+    assert_eq!(
+        "user=admin,password=!secret hunter2",
+        Serializer::new()
+            .reveal_secrets(true)
+            .to_string(&config)
+            .unwrap()
+    );
+    assert_eq!(config, roundtrip(&config));
+}
+
+#[test]
+fn serialize_unsupported_test() {
+    use crate::secret::Secret;
+    use crate::{acf_map, acf_seq};
+
+    let nested = acf_map! {
+        "db" => acf_map! {"hosts" => acf_seq!{"a", "b"}, "port" => 5432},
+    };
+    assert_eq!(
+        Err(SerializeError::Nested {
+            pointer: "/db/hosts".to_string()
+        }),
+        Serializer::new().to_string(&nested)
+    );
+    assert_eq!("db={hosts: {a, b}, port: 5432}", nested.to_string());
+
+    assert_eq!(
+        Err(SerializeError::Nested {
+            pointer: "/0".to_string()
+        }),
+        Serializer::new().to_string(&acf_seq! {acf_seq!{1, acf_seq!{2}}})
+    );
+    let secret = acf_map! {"s" => Secret::new(acf_seq!{1})};
+    assert_eq!(
+        Err(SerializeError::Nested {
+            pointer: "/s".to_string()
+        }),
+        Serializer::new().reveal_secrets(true).to_string(&secret)
+    );
+    assert!(Serializer::new().to_string(&secret).is_ok());
+
+    assert_eq!(
+        Err(SerializeError::NaN {
+            pointer: "/x/0".to_string()
+        }),
+        Serializer::new().to_string(&acf_map! {"x" => acf_seq!{f64::NAN}})
+    );
+    assert_eq!(
+        "x={NaN}",
+        format!("{}", acf_map! {"x" => acf_seq!{f64::NAN}})
+    );
+    let infinite = acf_map! {"a" => f64::INFINITY, "b" => acf_seq!{f64::NEG_INFINITY}};
+    assert_eq!(
+        "a=1e400,b={-1e400}",
+        Serializer::new().to_string(&infinite).unwrap()
+    );
+    assert_eq!(infinite, roundtrip(&infinite));
+}
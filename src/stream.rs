@@ -0,0 +1,572 @@
+//! Pull parser that reads a document from [`std::io::Read`] in chunks and yields [`Event`]s,
+//! so large documents can be processed without holding the whole input in memory. Only the
+//! value that is being parsed is buffered.
+//!
+//! The grammar is the same as [`crate::parser::tokenize_ast`], and so are the error offsets.
+//! Like winnow, an error inside a quoted string or after `!secret` is reported where it
+//! happens, any other error at the end of the last complete top-level item.
+
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read};
+
+use snailquote::unescape;
+
+use crate::parser::{primative_value, SPECIAL_CHARS};
+use crate::{Secret, StringMap, ACF};
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// Start of the document, or of a map value.
+    StartMap,
+    EndMap,
+    StartSeq,
+    EndSeq,
+    /// Key of the next value in the current map.
+    Key(String),
+    /// A scalar, secrets are [`ACF::Secret`].
+    Scalar(ACF),
+}
+
+#[derive(Debug)]
+pub enum StreamError {
+    Io(std::io::Error),
+    /// The input is not valid UTF-8 at byte `offset`.
+    Utf8 {
+        offset: usize,
+    },
+    /// The input is not valid, `offset` is the byte position of the error.
+    Syntax {
+        offset: usize,
+    },
+}
+
+impl std::fmt::Display for StreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamError::Io(error) => error.fmt(f),
+            StreamError::Utf8 { offset } => write!(f, "{offset}: invalid UTF-8"),
+            StreamError::Syntax { offset } => write!(f, "{offset}: invalid syntax"),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StreamError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for StreamError {
+    fn from(error: std::io::Error) -> Self {
+        StreamError::Io(error)
+    }
+}
+
+/// Decodes characters from a reader, keeping only the unread part of the last chunk.
+struct Source<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    start: usize,
+    end: usize,
+    /// Bytes consumed so far.
+    offset: usize,
+    peeked: Option<char>,
+}
+
+impl<R: Read> Source<R> {
+    /// Makes sure at least `count` bytes are buffered, unless the input ends first.
+    fn fill(&mut self, count: usize) -> Result<(), StreamError> {
+        if self.end - self.start >= count {
+            return Ok(());
+        }
+        self.buffer.copy_within(self.start..self.end, 0);
+        self.end -= self.start;
+        self.start = 0;
+
+        while self.end < count {
+            match self.reader.read(&mut self.buffer[self.end..]) {
+                Ok(0) => break,
+                Ok(read) => self.end += read,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+        Ok(())
+    }
+
+    fn peek(&mut self) -> Result<Option<char>, StreamError> {
+        if self.peeked.is_some() {
+            return Ok(self.peeked);
+        }
+
+        self.fill(1)?;
+        let Some(&first) = self.buffer[self.start..self.end].first() else {
+            return Ok(None);
+        };
+        let width = match first {
+            0x00..=0x7F => 1,
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => 0,
+        };
+        self.fill(width)?;
+
+        let bytes = &self.buffer[self.start..self.end];
+        self.peeked = bytes
+            .get(..width)
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+            .and_then(|text| text.chars().next());
+        match self.peeked {
+            Some(_) => Ok(self.peeked),
+            None => Err(StreamError::Utf8 {
+                offset: self.offset,
+            }),
+        }
+    }
+
+    fn bump(&mut self) {
+        let ch = self.peeked.take().expect("peek before bump");
+        self.start += ch.len_utf8();
+        self.offset += ch.len_utf8();
+    }
+
+    fn skip_ws(&mut self) -> Result<(), StreamError> {
+        while let Some(' ' | '\t' | '\r' | '\n') = self.peek()? {
+            self.bump();
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Start,
+    /// Before a top-level `key=value`.
+    Item,
+    /// After a top-level value, expecting `,` or the end.
+    AfterItem,
+    /// After an item of a map value, expecting `,` or `}`.
+    Map,
+    /// After an item of a sequence value, expecting `,` or `}`.
+    Seq,
+    Done,
+}
+
+/// Iterator over the events of a document. The document is a map, so the first event is
+/// [`Event::StartMap`] and the last [`Event::EndMap`]. Stops after the first error.
+pub struct EventReader<R> {
+    source: Source<R>,
+    state: State,
+    events: VecDeque<Event>,
+    /// Where errors outside of strings and secrets are reported.
+    item_end: usize,
+}
+
+impl<R: Read> EventReader<R> {
+    pub fn new(reader: R) -> Self {
+        EventReader {
+            source: Source {
+                reader,
+                buffer: vec![0; CHUNK_SIZE],
+                start: 0,
+                end: 0,
+                offset: 0,
+                peeked: None,
+            },
+            state: State::Start,
+            events: VecDeque::new(),
+            item_end: 0,
+        }
+    }
+
+    /// Bytes read so far.
+    pub fn offset(&self) -> usize {
+        self.source.offset
+    }
+
+    fn step(&mut self) -> Result<(), StreamError> {
+        match self.state {
+            State::Start => {
+                self.events.push_back(Event::StartMap);
+                self.source.skip_ws()?;
+                self.item_end = self.source.offset;
+                self.state = State::Item;
+                if self.source.peek()?.is_none() {
+                    self.events.push_back(Event::EndMap);
+                    self.state = State::Done;
+                }
+            }
+            State::Item => self.item()?,
+            State::AfterItem => match self.source.peek()? {
+                None => {
+                    self.events.push_back(Event::EndMap);
+                    self.state = State::Done;
+                }
+                Some(',') => {
+                    self.source.bump();
+                    self.state = State::Item;
+                }
+                Some(_) => return Err(self.invalid()),
+            },
+            State::Map | State::Seq => {
+                self.source.skip_ws()?;
+                match self.source.peek()? {
+                    Some(',') => {
+                        self.source.bump();
+                        self.source.skip_ws()?;
+                        if self.state == State::Map {
+                            let key = self.primative()?.ok_or_else(|| self.invalid())?;
+                            self.source.skip_ws()?;
+                            self.expect(':')?;
+                            self.events.push_back(Event::Key(to_key(&key)));
+                        }
+                        let value = self.scalar()?;
+                        self.events.push_back(Event::Scalar(value));
+                    }
+                    Some('}') => {
+                        self.source.bump();
+                        self.events.push_back(match self.state {
+                            State::Map => Event::EndMap,
+                            _ => Event::EndSeq,
+                        });
+                        self.end_item()?;
+                    }
+                    _ => return Err(self.invalid()),
+                }
+            }
+            State::Done => {}
+        }
+        Ok(())
+    }
+
+    fn item(&mut self) -> Result<(), StreamError> {
+        self.source.skip_ws()?;
+        let key = self.primative()?.ok_or_else(|| self.invalid())?;
+        self.source.skip_ws()?;
+        self.expect('=')?;
+        self.events.push_back(Event::Key(to_key(&key)));
+
+        if self.source.peek()? != Some('{') {
+            let value = self.scalar()?;
+            self.events.push_back(Event::Scalar(value));
+            return self.end_item();
+        }
+
+        self.source.bump();
+        self.source.skip_ws()?;
+        let first = self.primative()?.ok_or_else(|| self.invalid())?;
+        let secret = first == "!secret" && self.next_is_space()?;
+        self.source.skip_ws()?;
+
+        if self.source.peek()? == Some(':') {
+            self.source.bump();
+            self.source.skip_ws()?;
+            self.events.push_back(Event::StartMap);
+            self.events.push_back(Event::Key(to_key(&first)));
+            let value = self.scalar()?;
+            self.events.push_back(Event::Scalar(value));
+            self.state = State::Map;
+        } else {
+            self.events.push_back(Event::StartSeq);
+            let value = match secret {
+                true => self.secret()?,
+                false => primative_value(&first),
+            };
+            self.events.push_back(Event::Scalar(value));
+            self.state = State::Seq;
+        }
+        Ok(())
+    }
+
+    fn end_item(&mut self) -> Result<(), StreamError> {
+        self.source.skip_ws()?;
+        self.item_end = self.source.offset;
+        self.state = State::AfterItem;
+        Ok(())
+    }
+
+    /// Takes the expected character and the whitespace after it.
+    fn expect(&mut self, expected: char) -> Result<(), StreamError> {
+        if self.source.peek()? != Some(expected) {
+            return Err(self.invalid());
+        }
+        self.source.bump();
+        self.source.skip_ws()
+    }
+
+    fn scalar(&mut self) -> Result<ACF, StreamError> {
+        let text = self.primative()?.ok_or_else(|| self.invalid())?;
+        if text == "!secret" && self.next_is_space()? {
+            self.source.skip_ws()?;
+            return self.secret();
+        }
+        Ok(primative_value(&text))
+    }
+
+    /// The value after `!secret`, it has to be there.
+    fn secret(&mut self) -> Result<ACF, StreamError> {
+        let text = self.primative()?.ok_or(StreamError::Syntax {
+            offset: self.source.offset,
+        })?;
+        Ok(ACF::Secret(Secret::new(Box::new(primative_value(&text)))))
+    }
+
+    /// Reads a quoted string, including the quotes, or an unquoted value. `None` when there
+    /// is no value at this position.
+    fn primative(&mut self) -> Result<Option<String>, StreamError> {
+        let mut text = String::new();
+
+        if self.source.peek()? != Some('"') {
+            while let Some(ch) = self.source.peek()? {
+                if ch.is_whitespace() || SPECIAL_CHARS.contains(&ch) {
+                    break;
+                }
+                text.push(ch);
+                self.source.bump();
+            }
+            return Ok((!text.is_empty()).then_some(text));
+        }
+
+        text.push('"');
+        self.source.bump();
+        loop {
+            let invalid = StreamError::Syntax {
+                offset: self.source.offset,
+            };
+            let ch = self.source.peek()?.ok_or(invalid)?;
+            text.push(ch);
+            self.source.bump();
+
+            match ch {
+                '"' => return Ok(Some(text)),
+                '\\' => {
+                    let invalid = StreamError::Syntax {
+                        offset: self.source.offset,
+                    };
+                    match self.source.peek()? {
+                        Some(ch @ ('"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't')) => {
+                            text.push(ch);
+                            self.source.bump();
+                        }
+                        _ => return Err(invalid),
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn next_is_space(&mut self) -> Result<bool, StreamError> {
+        Ok(matches!(
+            self.source.peek()?,
+            Some(' ' | '\t' | '\r' | '\n')
+        ))
+    }
+
+    fn invalid(&self) -> StreamError {
+        StreamError::Syntax {
+            offset: self.item_end,
+        }
+    }
+}
+
+fn to_key(text: &str) -> String {
+    unescape(text).unwrap_or_default()
+}
+
+impl<R: Read> Iterator for EventReader<R> {
+    type Item = Result<Event, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.events.is_empty() && self.state != State::Done {
+            if let Err(error) = self.step() {
+                self.state = State::Done;
+                self.events.clear();
+                return Some(Err(error));
+            }
+        }
+        self.events.pop_front().map(Ok)
+    }
+}
+
+/// Reads a whole document, same as parsing it with [`crate::parser::tokenize_ast`].
+pub fn from_reader<R: Read>(reader: R) -> Result<ACF, StreamError> {
+    let mut stack: Vec<(Option<String>, ACF)> = Vec::new();
+    let mut key = None;
+
+    for event in EventReader::new(reader) {
+        let (key, value) = match event? {
+            Event::StartMap => {
+                stack.push((key.take(), ACF::Map(StringMap::default())));
+                continue;
+            }
+            Event::StartSeq => {
+                stack.push((key.take(), ACF::Seq(Vec::new())));
+                continue;
+            }
+            Event::Key(next) => {
+                key = Some(next);
+                continue;
+            }
+            Event::Scalar(value) => (key.take(), value),
+            Event::EndMap | Event::EndSeq => stack.pop().expect("events are balanced"),
+        };
+
+        match (stack.last_mut(), key) {
+            (None, _) => return Ok(value),
+            (Some((_, ACF::Map(map))), Some(key)) => {
+                map.insert(key, value);
+            }
+            (Some((_, ACF::Seq(values))), _) => values.push(value),
+            _ => unreachable!("map values have a key"),
+        }
+    }
+    unreachable!("the document ends with EndMap or an error")
+}
+
+/// Reads one byte at a time, to test values split over several reads.
+#[cfg(test)]
+struct Trickle<'a>(&'a [u8]);
+
+#[cfg(test)]
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let Some((first, rest)) = self.0.split_first() else {
+            return Ok(0);
+        };
+        buf[0] = *first;
+        self.0 = rest;
+        Ok(1)
+    }
+}
+
+#[test]
+fn stream_events_test() {
+    let input = "a = 1, b={x: \"y z\", w: !secret 2},c={1, é}";
+    let events: Vec<_> = EventReader::new(Trickle(input.as_bytes()))
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(
+        vec![
+            Event::StartMap,
+            Event::Key("a".to_string()),
+            Event::Scalar(ACF::Integer(1)),
+            Event::Key("b".to_string()),
+            Event::StartMap,
+            Event::Key("x".to_string()),
+            Event::Scalar(ACF::from("y z")),
+            Event::Key("w".to_string()),
+            Event::Scalar(ACF::Secret(Secret::new(Box::new(ACF::Integer(2))))),
+            Event::EndMap,
+            Event::Key("c".to_string()),
+            Event::StartSeq,
+            Event::Scalar(ACF::Integer(1)),
+            Event::Scalar(ACF::from("é")),
+            Event::EndSeq,
+            Event::EndMap,
+        ],
+        events
+    );
+}
+
+#[test]
+fn stream_matches_tokenize_ast_test() {
+    let inputs = [
+        "",
+        " \n",
+        "a=1",
+        " a = 1 , b\t=\n\"two, 2\" ",
+        "config1={value: 1, default: 12, yes: true},config2={DEFAULT: \"testing\", extra: \"extra \\\"quotes\\\"\"},config3={false, 123, 1.23}",
+        "a={x: !secret 1, \"y z\": 'q'}, b={!secret \"p w\", e, 0.5, -3, 1_000}, c=!secret true",
+        "a={!secret : 1}, b=!secret, !secret = 2",
+        "a=é,ü={ß: ø}",
+        "dup=1,dup=2",
+        "a=1 b=2",
+        "a={}",
+        "a={1,}",
+        "a={x: {y: 1}}",
+        "a=!secret ",
+        "a=!secret ,",
+        "a={x: !secret }",
+        "a={!secret x: 1}",
+        "a=\"x",
+        "a=\"\\q\"",
+        "a=\"x\\",
+        "a=1,",
+        "a=1, \"b",
+        "a=1 \"b",
+        "a=",
+        "=1",
+        "a",
+        "a=}",
+        "a:1",
+        "a={x: 1, 2}",
+        "a={1, x: 2}",
+        "a={1 \"x",
+        "b=2, a={1,2",
+        "b=2, a={x: \"q",
+        "  a = 1 ,  b",
+        "a=x\u{a0}",
+        "a=b, c={d:e}}",
+        "a=1\n  b=2",
+    ];
+
+    for input in inputs {
+        let expected = crate::parser::tokenize_ast(input)
+            .map(|tokens| crate::tokenized_to_config(input, tokens))
+            .map_err(|e| e.offset());
+        for reader in [
+            Box::new(input.as_bytes()) as Box<dyn Read>,
+            Box::new(Trickle(input.as_bytes())),
+        ] {
+            let found = from_reader(reader).map_err(|e| match e {
+                StreamError::Syntax { offset } => offset,
+                e => panic!("{e}"),
+            });
+            assert_eq!(expected, found, "{input:?}");
+        }
+    }
+}
+
+#[test]
+fn stream_fragments_match_tokenize_ast_test() {
+    for input in crate::handwritten::fragment_inputs(20_000) {
+        let expected = crate::parser::tokenize_ast(&input)
+            .map(|tokens| crate::tokenized_to_config(&input, tokens))
+            .map_err(|e| e.offset());
+        let found = from_reader(input.as_bytes()).map_err(|e| match e {
+            StreamError::Syntax { offset } => offset,
+            e => panic!("{e}"),
+        });
+        assert_eq!(expected, found, "{input:?}");
+    }
+}
+
+#[test]
+fn stream_large_input_test() {
+    let input: String = (0..5_000)
+        .map(|index| {
+            format!(
+                "key{index}={{x: \"{}\", y: {index}}}",
+                "v".repeat(index % 50)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n");
+
+    let expected = crate::tokenized_to_config(&input, crate::parser::tokenize_ast(&input).unwrap());
+    assert_eq!(expected, from_reader(input.as_bytes()).unwrap());
+
+    let invalid = [b"a=\"".as_slice(), &[0xff], b"\""].concat();
+    assert!(matches!(
+        from_reader(invalid.as_slice()),
+        Err(StreamError::Utf8 { offset: 3 })
+    ));
+}
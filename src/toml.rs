@@ -0,0 +1,182 @@
+//! Conversion from and to TOML, enabled with the `toml` feature.
+//!
+//! TOML datetimes have no ACF counterpart and are rejected. Secrets are written as their
+//! plain value, since TOML has no way to mark them.
+
+use kstring::KStringCow;
+use toml::Value;
+
+use crate::selector::walk_pointer;
+use crate::{KeyIndexRef, Kind, ACF};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TomlError {
+    /// The input is not valid TOML.
+    Parse(String),
+    /// The value at `pointer` is a datetime.
+    Datetime {
+        pointer: String,
+        value: String,
+    },
+    /// Only maps can be written as a TOML document.
+    NotATable(Kind),
+    Serialize(String),
+}
+
+impl std::fmt::Display for TomlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TomlError::Parse(message) => write!(f, "invalid TOML: {message}"),
+            TomlError::Datetime { pointer, value } => write!(
+                f,
+                "datetime {value} at {pointer} is not supported, quote it to keep it as a string"
+            ),
+            TomlError::NotATable(kind) => {
+                write!(f, "a TOML document must be a map, found {kind}")
+            }
+            TomlError::Serialize(message) => write!(f, "can't write TOML: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for TomlError {}
+
+impl TryFrom<Value> for ACF {
+    type Error = TomlError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        from_toml(value, &mut Vec::new())
+    }
+}
+
+fn from_toml(value: Value, path: &mut Vec<KeyIndexRef<'static>>) -> Result<ACF, TomlError> {
+    Ok(match value {
+        Value::String(string) => ACF::from(string),
+        Value::Integer(integer) => ACF::from(integer),
+        Value::Float(float) => ACF::from(float),
+        Value::Boolean(boolean) => ACF::from(boolean),
+        Value::Datetime(datetime) => {
+            return Err(TomlError::Datetime {
+                pointer: walk_pointer(path.iter()),
+                value: datetime.to_string(),
+            })
+        }
+        Value::Array(values) => ACF::Seq(
+            values
+                .into_iter()
+                .enumerate()
+                .map(|(index, value)| {
+                    path.push(KeyIndexRef::Integer(index as isize));
+                    let out = from_toml(value, path);
+                    path.pop();
+                    out
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Table(table) => ACF::Map(
+            table
+                .into_iter()
+                .map(|(key, value)| {
+                    path.push(KeyIndexRef::String(KStringCow::from_string(key.clone())));
+                    let out = from_toml(value, path);
+                    path.pop();
+                    Ok((key, out?))
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+    })
+}
+
+impl From<ACF> for Value {
+    fn from(config: ACF) -> Self {
+        match config {
+            ACF::String(string) => Value::String(string.to_string()),
+            ACF::Integer(integer) => Value::Integer(integer),
+            ACF::Float(float) => Value::Float(float.into_inner()),
+            ACF::Boolean(boolean) => Value::Boolean(boolean),
+            ACF::Secret(secret) => Value::from(*secret.into_exposed()),
+            ACF::Seq(values) => Value::Array(values.into_iter().map(Value::from).collect()),
+            ACF::Map(map) => Value::Table(
+                map.into_iter()
+                    .map(|(key, value)| (key, Value::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+pub fn from_str(input: &str) -> Result<ACF, TomlError> {
+    let table: toml::Table = input
+        .parse()
+        .map_err(|e: toml::de::Error| TomlError::Parse(e.message().to_string()))?;
+    ACF::try_from(Value::Table(table))
+}
+
+pub fn to_string(config: &ACF) -> Result<String, TomlError> {
+    match Value::from(config.clone()) {
+        Value::Table(table) => {
+            toml::to_string(&table).map_err(|e| TomlError::Serialize(e.to_string()))
+        }
+        _ => Err(TomlError::NotATable(config.kind())),
+    }
+}
+
+#[test]
+fn toml_roundtrip_test() {
+    use crate::{acf_map, acf_seq};
+
+    let input = r#"
+name = "app"
+ratio = 0.5
+
+[db]
+hosts = ["a", "b"]
+port = 5432
+"#;
+    let config = from_str(input).unwrap();
+
+    assert_eq!(
+        acf_map! {
+            "name" => "app",
+            "ratio" => 0.5,
+            "db" => acf_map! {"hosts" => acf_seq!{"a", "b"}, "port" => 5432},
+        },
+        config
+    );
+    assert_eq!(Ok(config.clone()), from_str(&to_string(&config).unwrap()));
+}
+
+#[test]
+fn toml_to_acf_test() {
+    use crate::ser::{SerializeError, Serializer};
+
+    let serializer = Serializer::new().reveal_secrets(true);
+    let config = from_str("name = \"app\"\n[db]\nport = 5432\nhosts = \"a b\"\n").unwrap();
+    let text = serializer.to_string(&config).unwrap();
+    let tokens = crate::parser::tokenize_ast(&text).unwrap();
+    assert_eq!(config, crate::tokenized_to_config(&text, tokens));
+
+    let config = from_str("[db]\nhosts = [\"a\", \"b\"]\n[db.pool]\nsize = 4\n").unwrap();
+    assert_eq!(
+        Err(SerializeError::Nested {
+            pointer: "/db/hosts".to_string()
+        }),
+        serializer.to_string(&config)
+    );
+}
+
+#[test]
+fn toml_errors_test() {
+    assert_eq!(
+        Err(TomlError::Datetime {
+            pointer: "/db/created/1".to_string(),
+            value: "1979-05-27".to_string(),
+        }),
+        from_str("db = { created = [1, 1979-05-27] }")
+    );
+    assert!(matches!(from_str("a = "), Err(TomlError::Parse(_))));
+    assert_eq!(
+        Err(TomlError::NotATable(Kind::Integer)),
+        to_string(&ACF::from(1))
+    );
+}
@@ -0,0 +1,254 @@
+use kstring::KStringCow;
+
+use crate::{KeyIndexRef, StringMap, ACF};
+
+/// Called while walking an [`ACF`] in document order. Secrets are leaves, their content is not visited.
+#[allow(unused_variables)]
+pub trait Visitor<'a> {
+    fn visit_leaf(&mut self, path: &[KeyIndexRef<'a>], value: &'a ACF) {}
+
+    fn enter_map(&mut self, path: &[KeyIndexRef<'a>], map: &'a StringMap<ACF>) {}
+
+    fn leave_map(&mut self, path: &[KeyIndexRef<'a>], map: &'a StringMap<ACF>) {}
+
+    fn enter_seq(&mut self, path: &[KeyIndexRef<'a>], values: &'a [ACF]) {}
+
+    fn leave_seq(&mut self, path: &[KeyIndexRef<'a>], values: &'a [ACF]) {}
+}
+
+/// Like [`Visitor`], but allows changing values. Maps and sequences are visited after
+/// `enter_*` returns, so entries added there are visited as well.
+#[allow(unused_variables)]
+pub trait VisitorMut {
+    fn visit_leaf(&mut self, path: &[KeyIndexRef<'_>], value: &mut ACF) {}
+
+    fn enter_map(&mut self, path: &[KeyIndexRef<'_>], map: &mut StringMap<ACF>) {}
+
+    fn leave_map(&mut self, path: &[KeyIndexRef<'_>], map: &mut StringMap<ACF>) {}
+
+    fn enter_seq(&mut self, path: &[KeyIndexRef<'_>], values: &mut Vec<ACF>) {}
+
+    fn leave_seq(&mut self, path: &[KeyIndexRef<'_>], values: &mut Vec<ACF>) {}
+}
+
+pub fn walk<'a, V: Visitor<'a> + ?Sized>(config: &'a ACF, visitor: &mut V) {
+    let mut path = Vec::new();
+    walk_inner(config, &mut path, visitor);
+}
+
+fn walk_inner<'a, V: Visitor<'a> + ?Sized>(
+    config: &'a ACF,
+    path: &mut Vec<KeyIndexRef<'a>>,
+    visitor: &mut V,
+) {
+    match config {
+        ACF::Map(map) => {
+            visitor.enter_map(path, map);
+            for (key, value) in map.iter() {
+                path.push(KeyIndexRef::String(KStringCow::from_ref(key)));
+                walk_inner(value, path, visitor);
+                path.pop();
+            }
+            visitor.leave_map(path, map);
+        }
+        ACF::Seq(values) => {
+            visitor.enter_seq(path, values);
+            for (index, value) in values.iter().enumerate() {
+                path.push(KeyIndexRef::Integer(index as isize));
+                walk_inner(value, path, visitor);
+                path.pop();
+            }
+            visitor.leave_seq(path, values);
+        }
+        leaf => visitor.visit_leaf(path, leaf),
+    }
+}
+
+pub fn walk_mut<V: VisitorMut + ?Sized>(config: &mut ACF, visitor: &mut V) {
+    let mut path = Vec::new();
+    walk_mut_inner(config, &mut path, visitor);
+}
+
+fn walk_mut_inner<V: VisitorMut + ?Sized>(
+    config: &mut ACF,
+    path: &mut Vec<KeyIndexRef<'static>>,
+    visitor: &mut V,
+) {
+    match config {
+        ACF::Map(map) => {
+            visitor.enter_map(path, map);
+            for (key, value) in map.iter_mut() {
+                path.push(KeyIndexRef::String(KStringCow::from_string(key.clone())));
+                walk_mut_inner(value, path, visitor);
+                path.pop();
+            }
+            visitor.leave_map(path, map);
+        }
+        ACF::Seq(values) => {
+            visitor.enter_seq(path, values);
+            for (index, value) in values.iter_mut().enumerate() {
+                path.push(KeyIndexRef::Integer(index as isize));
+                walk_mut_inner(value, path, visitor);
+                path.pop();
+            }
+            visitor.leave_seq(path, values);
+        }
+        leaf => visitor.visit_leaf(path, leaf),
+    }
+}
+
+/// Iterator over every leaf and its path, see [`ACF::walk`].
+pub struct Walk<'a> {
+    stack: Vec<(Vec<KeyIndexRef<'a>>, &'a ACF)>,
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = (Vec<KeyIndexRef<'a>>, &'a ACF);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((path, config)) = self.stack.pop() {
+            let child = |key| {
+                let mut path = path.clone();
+                path.push(key);
+                path
+            };
+
+            match config {
+                ACF::Map(map) => self.stack.extend(map.iter().rev().map(|(key, value)| {
+                    (child(KeyIndexRef::String(KStringCow::from_ref(key))), value)
+                })),
+                ACF::Seq(values) => {
+                    self.stack.extend(
+                        values.iter().enumerate().rev().map(|(index, value)| {
+                            (child(KeyIndexRef::Integer(index as isize)), value)
+                        }),
+                    )
+                }
+                leaf => return Some((path, leaf)),
+            }
+        }
+
+        None
+    }
+}
+
+impl ACF {
+    /// Every leaf value with its path, in document order.
+    pub fn walk(&self) -> Walk<'_> {
+        Walk {
+            stack: vec![(Vec::new(), self)],
+        }
+    }
+}
+
+#[cfg(test)]
+fn example() -> ACF {
+    use crate::{acf_map, acf_seq, Secret};
+
+    acf_map! {
+        "db" => acf_map! {
+            "hosts" => acf_seq!{"a", "b"},
+            "password" => Secret::new("hunter2"),
+        },
+        "port" => 80,
+    }
+}
+
+#[test]
+fn walk_test() {
+    let config = example();
+
+    let leaves: Vec<_> = config
+        .walk()
+        .map(|(path, value)| (crate::selector::to_path(&path), value.to_string()))
+        .collect();
+
+    assert_eq!(
+        vec![
+            ("db.hosts[0]".to_string(), "a".to_string()),
+            ("db.hosts[1]".to_string(), "b".to_string()),
+            (
+                "db.password".to_string(),
+                "!secret \"[REDACTED]\"".to_string()
+            ),
+            ("port".to_string(), "80".to_string()),
+        ],
+        leaves
+    );
+}
+
+#[test]
+fn visitor_test() {
+    struct Events(Vec<String>);
+
+    impl<'a> Visitor<'a> for Events {
+        fn visit_leaf(&mut self, path: &[KeyIndexRef<'a>], _: &'a ACF) {
+            self.0.push(crate::selector::walk_pointer(path));
+        }
+
+        fn enter_map(&mut self, path: &[KeyIndexRef<'a>], _: &'a StringMap<ACF>) {
+            self.0
+                .push(format!("{{{}", crate::selector::walk_pointer(path)));
+        }
+
+        fn leave_map(&mut self, _: &[KeyIndexRef<'a>], _: &'a StringMap<ACF>) {
+            self.0.push("}".to_string());
+        }
+
+        fn enter_seq(&mut self, path: &[KeyIndexRef<'a>], _: &'a [ACF]) {
+            self.0
+                .push(format!("[{}", crate::selector::walk_pointer(path)));
+        }
+
+        fn leave_seq(&mut self, _: &[KeyIndexRef<'a>], _: &'a [ACF]) {
+            self.0.push("]".to_string());
+        }
+    }
+
+    let config = example();
+    let mut events = Events(Vec::new());
+    walk(&config, &mut events);
+
+    assert_eq!(
+        vec![
+            "{",
+            "{/db",
+            "[/db/hosts",
+            "/db/hosts/0",
+            "/db/hosts/1",
+            "]",
+            "/db/password",
+            "}",
+            "/port",
+            "}"
+        ],
+        events.0
+    );
+}
+
+#[test]
+fn visitor_mut_test() {
+    use crate::{acf_map, acf_seq};
+
+    struct Upper;
+
+    impl VisitorMut for Upper {
+        fn visit_leaf(&mut self, _: &[KeyIndexRef<'_>], value: &mut ACF) {
+            if let ACF::String(string) = value {
+                *value = ACF::from(string.to_uppercase());
+            }
+        }
+
+        fn enter_seq(&mut self, _: &[KeyIndexRef<'_>], values: &mut Vec<ACF>) {
+            values.push(ACF::from("c"));
+        }
+    }
+
+    let mut config = acf_map! {"hosts" => acf_seq!{"a", "b"}, "port" => 80};
+    walk_mut(&mut config, &mut Upper);
+
+    assert_eq!(
+        acf_map! {"hosts" => acf_seq!{"A", "B", "C"}, "port" => 80},
+        config
+    );
+}
@@ -0,0 +1,290 @@
+//! Conversion from and to YAML, enabled with the `yaml` feature.
+//!
+//! Anchors and aliases are rejected instead of copied, `null` has no ACF counterpart and only
+//! scalars can be used as keys. Secrets are written as their plain value.
+
+use kstring::KStringCow;
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+use yaml_rust2::{Yaml, YamlEmitter, YamlLoader};
+
+use crate::selector::walk_pointer;
+use crate::{KeyIndexRef, ACF};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum YamlError {
+    /// The input is not valid YAML.
+    Parse(String),
+    /// An anchor or alias at this 1-based line and column.
+    Anchor {
+        line: usize,
+        column: usize,
+    },
+    /// The value at `pointer` is `null`.
+    Null {
+        pointer: String,
+    },
+    /// The value at `pointer` is an alias, for a [`Yaml`] that was not read by [`from_str`].
+    Alias {
+        pointer: String,
+    },
+    /// The value at `pointer` is a [`Yaml::BadValue`].
+    BadValue {
+        pointer: String,
+    },
+    /// The map at `pointer` has a key that is not a scalar.
+    Key {
+        pointer: String,
+    },
+    /// The input contains more than one document.
+    Documents(usize),
+    Serialize(String),
+}
+
+impl std::fmt::Display for YamlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            YamlError::Parse(message) => write!(f, "invalid YAML: {message}"),
+            YamlError::Anchor { line, column } => write!(
+                f,
+                "{line}:{column}: anchors and aliases are not supported, repeat the value instead"
+            ),
+            YamlError::Null { pointer } => write!(f, "null at {pointer} is not supported"),
+            YamlError::Alias { pointer } => write!(f, "alias at {pointer} is not supported"),
+            YamlError::BadValue { pointer } => write!(f, "invalid value at {pointer}"),
+            YamlError::Key { pointer } => {
+                write!(f, "map at {pointer} has a key that is not a scalar")
+            }
+            YamlError::Documents(count) => {
+                write!(f, "expected a single document, found {count}")
+            }
+            YamlError::Serialize(message) => write!(f, "can't write YAML: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for YamlError {}
+
+impl TryFrom<Yaml> for ACF {
+    type Error = YamlError;
+
+    fn try_from(value: Yaml) -> Result<Self, Self::Error> {
+        from_yaml(value, &mut Vec::new())
+    }
+}
+
+fn from_yaml(value: Yaml, path: &mut Vec<KeyIndexRef<'static>>) -> Result<ACF, YamlError> {
+    Ok(match value {
+        Yaml::String(string) => ACF::from(string),
+        Yaml::Integer(integer) => ACF::from(integer),
+        Yaml::Real(ref real) => match value.as_f64() {
+            Some(float) => ACF::from(float),
+            None => ACF::from(real.as_str()),
+        },
+        Yaml::Boolean(boolean) => ACF::from(boolean),
+        Yaml::Array(values) => ACF::Seq(
+            values
+                .into_iter()
+                .enumerate()
+                .map(|(index, value)| {
+                    path.push(KeyIndexRef::Integer(index as isize));
+                    let out = from_yaml(value, path);
+                    path.pop();
+                    out
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        Yaml::Hash(hash) => ACF::Map(
+            hash.into_iter()
+                .map(|(key, value)| {
+                    let key = match key {
+                        Yaml::String(key) | Yaml::Real(key) => key,
+                        Yaml::Integer(key) => key.to_string(),
+                        Yaml::Boolean(key) => key.to_string(),
+                        _ => {
+                            return Err(YamlError::Key {
+                                pointer: walk_pointer(path.iter()),
+                            })
+                        }
+                    };
+                    path.push(KeyIndexRef::String(KStringCow::from_string(key.clone())));
+                    let out = from_yaml(value, path);
+                    path.pop();
+                    Ok((key, out?))
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        Yaml::Null => {
+            return Err(YamlError::Null {
+                pointer: walk_pointer(path.iter()),
+            })
+        }
+        Yaml::Alias(_) => {
+            return Err(YamlError::Alias {
+                pointer: walk_pointer(path.iter()),
+            })
+        }
+        Yaml::BadValue => {
+            return Err(YamlError::BadValue {
+                pointer: walk_pointer(path.iter()),
+            })
+        }
+    })
+}
+
+impl From<ACF> for Yaml {
+    fn from(config: ACF) -> Self {
+        match config {
+            ACF::String(string) => Yaml::String(string.to_string()),
+            ACF::Integer(integer) => Yaml::Integer(integer),
+            ACF::Float(float) => Yaml::Real(match float.into_inner() {
+                float if float.is_nan() => ".nan".to_string(),
+                float if float == f64::INFINITY => ".inf".to_string(),
+                float if float == f64::NEG_INFINITY => "-.inf".to_string(),
+                float => format!("{float:?}"),
+            }),
+            ACF::Boolean(boolean) => Yaml::Boolean(boolean),
+            ACF::Secret(secret) => Yaml::from(*secret.into_exposed()),
+            ACF::Seq(values) => Yaml::Array(values.into_iter().map(Yaml::from).collect()),
+            ACF::Map(map) => Yaml::Hash(
+                map.into_iter()
+                    .map(|(key, value)| (Yaml::String(key), Yaml::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Finds the first anchor or alias, the loader would silently copy them.
+struct AnchorFinder(Option<Marker>);
+
+impl MarkedEventReceiver for AnchorFinder {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        let anchor = match event {
+            Event::Alias(_) => true,
+            Event::Scalar(_, _, anchor, _)
+            | Event::SequenceStart(anchor, _)
+            | Event::MappingStart(anchor, _) => anchor > 0,
+            _ => false,
+        };
+        if anchor && self.0.is_none() {
+            self.0 = Some(mark);
+        }
+    }
+}
+
+/// Parses a single document, an empty input is an empty map.
+pub fn from_str(input: &str) -> Result<ACF, YamlError> {
+    let mut finder = AnchorFinder(None);
+    Parser::new_from_str(input)
+        .load(&mut finder, true)
+        .map_err(|e| YamlError::Parse(e.to_string()))?;
+    if let Some(mark) = finder.0 {
+        return Err(YamlError::Anchor {
+            line: mark.line(),
+            column: mark.col() + 1,
+        });
+    }
+
+    let mut documents =
+        YamlLoader::load_from_str(input).map_err(|e| YamlError::Parse(e.to_string()))?;
+    match documents.len() {
+        0 => Ok(ACF::Map(Default::default())),
+        1 => ACF::try_from(documents.remove(0)),
+        count => Err(YamlError::Documents(count)),
+    }
+}
+
+pub fn to_string(config: &ACF) -> Result<String, YamlError> {
+    let mut out = String::new();
+    YamlEmitter::new(&mut out)
+        .dump(&Yaml::from(config.clone()))
+        .map_err(|e| YamlError::Serialize(format!("{e:?}")))?;
+    let mut out = out.strip_prefix("---\n").unwrap_or(&out).to_string();
+    out.push('\n');
+    Ok(out)
+}
+
+#[test]
+fn yaml_roundtrip_test() {
+    use crate::{acf_map, acf_seq};
+
+    let input = "
+name: app
+ratio: 0.5
+version: '12'
+db:
+  hosts: [a, b]
+  port: 5432
+  8080: true
+";
+    let config = from_str(input).unwrap();
+
+    assert_eq!(
+        acf_map! {
+            "name" => "app",
+            "ratio" => 0.5,
+            "version" => "12",
+            "db" => acf_map! {"hosts" => acf_seq!{"a", "b"}, "port" => 5432, "8080" => true},
+        },
+        config
+    );
+    assert_eq!(Ok(config.clone()), from_str(&to_string(&config).unwrap()));
+    assert_eq!(Ok(ACF::Map(Default::default())), from_str(""));
+}
+
+#[test]
+fn yaml_to_acf_test() {
+    use crate::ser::{SerializeError, Serializer};
+
+    let serializer = Serializer::new().reveal_secrets(true);
+    let config = from_str("name: app\ndb:\n  port: 5432\n  hosts: [a, b]\n").unwrap();
+    assert_eq!(
+        Err(SerializeError::Nested {
+            pointer: "/db/hosts".to_string()
+        }),
+        serializer.to_string(&config)
+    );
+
+    let config = from_str("name: app\nhosts: [a, 'b c']\ndb: {port: 5432}\n").unwrap();
+    let text = serializer.to_string(&config).unwrap();
+    let tokens = crate::parser::tokenize_ast(&text).unwrap();
+    assert_eq!(config, crate::tokenized_to_config(&text, tokens));
+}
+
+#[test]
+fn yaml_errors_test() {
+    assert_eq!(
+        Err(YamlError::Anchor {
+            line: 1,
+            column: 13
+        }),
+        from_str("base: &base {a: 1}\nother: *base")
+    );
+    assert_eq!(
+        Err(YamlError::Null {
+            pointer: "/db/hosts/1".to_string()
+        }),
+        from_str("db:\n  hosts: [a, ~]")
+    );
+    assert_eq!(
+        Err(YamlError::Key {
+            pointer: "/db".to_string()
+        }),
+        from_str("db: {[1, 2]: a}")
+    );
+    assert_eq!(
+        Err(YamlError::Alias {
+            pointer: "/0".to_string()
+        }),
+        ACF::try_from(Yaml::Array(vec![Yaml::Alias(0)]))
+    );
+    assert_eq!(
+        Err(YamlError::BadValue {
+            pointer: String::new()
+        }),
+        ACF::try_from(Yaml::BadValue)
+    );
+    assert_eq!(Err(YamlError::Documents(2)), from_str("a: 1\n---\nb: 2"));
+    assert!(matches!(from_str("a: [1"), Err(YamlError::Parse(_))));
+}